@@ -0,0 +1,93 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// The source is polled normally
+    Closed,
+    /// The source failed too often and is not polled until the cooldown has passed
+    Open,
+    /// The cooldown has passed, the next poll decides whether the breaker closes or opens again
+    HalfOpen,
+}
+
+impl Display for BreakerState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerState::Closed => write!(f, "closed"),
+            BreakerState::Open => write!(f, "open"),
+            BreakerState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// Counts the failed polls of a source in a row and opens once `threshold` is reached
+pub struct CircuitBreaker {
+    state: BreakerState,
+    failures: u32,
+    threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            state: BreakerState::Closed,
+            failures: 0,
+            threshold: threshold.max(1),
+            cooldown,
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Called once the cooldown of an open breaker has passed, the next poll is a probe
+    pub fn half_open(&mut self) -> Option<BreakerState> {
+        self.transition(BreakerState::HalfOpen)
+    }
+
+    /// Returns the new state if the success closed the breaker
+    pub fn record_success(&mut self) -> Option<BreakerState> {
+        self.failures = 0;
+        self.transition(BreakerState::Closed)
+    }
+
+    /// Returns the new state if the failure opened the breaker, a failed probe opens it right away
+    pub fn record_failure(&mut self) -> Option<BreakerState> {
+        self.failures = self.failures.saturating_add(1);
+
+        if self.state == BreakerState::HalfOpen || self.failures >= self.threshold {
+            self.transition(BreakerState::Open)
+        } else {
+            None
+        }
+    }
+
+    fn transition(&mut self, state: BreakerState) -> Option<BreakerState> {
+        if self.state == state {
+            return None;
+        }
+
+        self.state = state;
+        Some(state)
+    }
+}
+
+/// `base * 2^attempt`, randomly shortened by up to half so failing sources do not retry in lockstep
+pub fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(2u32.saturating_pow(attempt));
+    let jitter = RandomState::new().build_hasher().finish() % 1000;
+
+    delay.mul_f64(0.5 + jitter as f64 / 2000.0)
+}
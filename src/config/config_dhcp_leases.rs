@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{DhcpLeaseFile, DhcpLeaseFormat};
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct DhcpLeaseFileConfig {
+    path: String,
+    format: DhcpLeaseFormat,
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("dhcp_lease_file", build_dhcp_lease_file);
+}
+
+fn build_dhcp_lease_file(lease_file: DhcpLeaseFileConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    Ok(Box::new(DhcpLeaseFile::new(
+        &lease_file.path,
+        lease_file.format,
+        context.updates.clone(),
+    )))
+}
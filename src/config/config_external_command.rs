@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::ExternalCommand;
+use crate::Pollable;
+
+/// The command has to print one json encoded client per line to stdout
+#[derive(Deserialize)]
+pub struct ExternalCommandConfig {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("external_command", build_external_command);
+}
+
+fn build_external_command(command: ExternalCommandConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    Ok(Box::new(ExternalCommand::new(
+        &command.command,
+        command.args,
+        Duration::from_secs(command.timeout),
+    )))
+}
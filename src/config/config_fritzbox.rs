@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::FritzBox;
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct FritzBoxConfig {
+    ip: String,
+    user: String,
+    password: String,
+    location: String,
+    #[serde(default)]
+    mesh_nodes: Vec<FritzBoxMeshNodeConfig>,
+    /// Location of hosts not behind a mesh node by the interface they are connected with, e.g. `Ethernet` or `802.11`
+    #[serde(default)]
+    interface_locations: HashMap<String, String>,
+}
+
+/// A mesh node is identified either by its device name or its mac address
+#[derive(Deserialize)]
+struct FritzBoxMeshNodeConfig {
+    node: String,
+    location: String,
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("fritzbox", build_fritzbox);
+}
+
+fn build_fritzbox(fritzbox: FritzBoxConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let mesh_nodes: HashMap<String, String> = fritzbox.mesh_nodes.iter()
+        .map(|node| (node.node.to_lowercase(), node.location.to_string()))
+        .collect();
+
+    Ok(Box::new(FritzBox::new(
+        &fritzbox.ip,
+        &fritzbox.user,
+        &fritzbox.password,
+        &fritzbox.location,
+        mesh_nodes,
+        fritzbox.interface_locations,
+    )))
+}
@@ -0,0 +1,24 @@
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::Hostapd;
+use crate::Pollable;
+
+/// Every BSS has its own control socket, e.g. `/var/run/hostapd/wlan0`
+#[derive(Deserialize)]
+pub struct HostapdConfig {
+    socket: String,
+    location: String,
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("hostapd", build_hostapd);
+}
+
+fn build_hostapd(hostapd: HostapdConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    Ok(Box::new(Hostapd::new(
+        &hostapd.socket,
+        &hostapd.location,
+        context.updates.clone(),
+    )))
+}
@@ -3,8 +3,9 @@ use std::str::FromStr;
 use regex::Regex;
 use serde::Deserialize;
 
-use crate::config::{AppConfig, DeviceTypes};
+use crate::config::registry::{SourceContext, SourceRegistry};
 use crate::device::HPSwitch;
+use crate::Pollable;
 
 #[derive(Deserialize)]
 pub struct HPSwitchConfig {
@@ -18,44 +19,62 @@ struct PortRange {
     to: u8,
 }
 
-pub fn build_hp_switches(config: &AppConfig, devices: &mut Vec<DeviceTypes>) {
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("hp_switch", build_hp_switch);
+}
+
+fn build_hp_switch(switch_config: HPSwitchConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
     let ip_regex = Regex::new(r"^[0-9]{1,3}\.[0-9]{1,3}\.[0-9]{1,3}\.[0-9]{1,3}").unwrap();
 
-    if config.hp_switches.is_some() {
-        let switches = config.hp_switches.as_ref().unwrap();
-        for switch_config in switches.iter() {
-            if !ip_regex.is_match(&switch_config.ip) {
-                println!("{} is not a valid IPv4 Address", &switch_config.ip);
-                continue;
-            }
+    if !ip_regex.is_match(&switch_config.ip) {
+        return Err(format!("{} is not a valid IPv4 Address", &switch_config.ip));
+    }
 
-            let switch = HPSwitch::new(
-                &switch_config.ip,
-                get_port_list(switch_config),
-                &switch_config.location,
-            );
+    Ok(Box::new(HPSwitch::new(
+        &switch_config.ip,
+        get_port_list(&switch_config.ports),
+        &switch_config.location,
+    )))
+}
 
-            devices.push(DeviceTypes::HPSwitch(switch));
+/// Expands a port list like `1-10, 12` into the single port numbers
+pub(super) fn get_port_list(port_list: &str) -> Vec<String> {
+    let mut ports = Vec::new();
+    for port in port_list.split(',') {
+        if let Some(range) = generate_port_range(port) {
+            for port in range.from..range.to {
+                ports.push(port.to_string());
+            }
         }
-    } else {
-        println!("No HP Switches configured...skipping")
     }
+
+    ports
 }
 
-fn get_port_list(switch_config: &HPSwitchConfig) -> Vec<String> {
+/// Like `get_port_list`, but also accepts interface names, where a trailing number range
+/// like `GigabitEthernet1/0/1-24` expands to the single interfaces
+pub(super) fn get_interface_list(port_list: &str) -> Vec<String> {
+    let interface_range_regex = Regex::new(r"^(.*[^0-9])([0-9]+)-([0-9]+)$").unwrap();
     let mut ports = Vec::new();
-    for port in switch_config.ports.split(',') {
-        let range = generate_port_range(port);
-        if range.is_some() {
-            let range = range.unwrap();
 
-            for port in range.from..range.to {
-                ports.push(port.to_string());
+    for port in port_list.split(',').map(str::trim).filter(|port| !port.is_empty()) {
+        if port.chars().all(|char| char.is_ascii_digit() || char == '-') {
+            ports.extend(get_port_list(port));
+        } else if let Some(captures) = interface_range_regex.captures(port) {
+            let (Ok(from), Ok(to)) = (u32::from_str(&captures[2]), u32::from_str(&captures[3])) else {
+                println!("Invalid Port Configuration: {}", port);
+                continue;
+            };
+
+            for number in from..=to {
+                ports.push(format!("{}{}", &captures[1], number));
             }
+        } else {
+            ports.push(port.to_string());
         }
     }
 
-    return ports;
+    ports
 }
 
 fn generate_port_range(port_str: &str) -> Option<PortRange> {
@@ -67,29 +86,42 @@ fn generate_port_range(port_str: &str) -> Option<PortRange> {
     if range_regex.is_match(port_str) {
         let range: Vec<&str> = port_str.split('-').collect();
 
-        if (&range).len() == 2 {
+        if range.len() == 2 {
             let from = u8::from_str(range[0]);
             let to = u8::from_str(range[1]);
 
-            if from.is_ok() && to.is_ok() {
+            if let (Ok(from), Ok(to)) = (from, to) {
                 return Some(PortRange {
-                    from: from.unwrap(),
-                    to: to.unwrap() + 1,
+                    from,
+                    to: to + 1,
                 });
             }
         }
-    } else if port_regex.is_match(port_str) {
-        let port = u8::from_str(port_str);
-
-        if port.is_ok() {
-            let port = port.unwrap();
-            return Some(PortRange {
-                from: port,
-                to: port,
-            });
-        }
+    } else if port_regex.is_match(port_str)
+        && let Ok(port) = u8::from_str(port_str) {
+        return Some(PortRange {
+            from: port,
+            to: port + 1,
+        });
     }
 
     println!("Invalid Port Configuration: {}", port_str);
     None
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_single_ports_and_ranges() {
+        let range = generate_port_range("12").unwrap();
+        assert_eq!((range.from, range.to), (12, 13));
+
+        let range = generate_port_range(" 1-3 ").unwrap();
+        assert_eq!((range.from, range.to), (1, 4));
+
+        assert!(generate_port_range("1-").is_none());
+        assert_eq!(get_port_list("1-3, 12"), vec!["1", "2", "3", "12"]);
+    }
+}
@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::LinuxNeighbors;
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct LinuxNeighborsConfig {
+    #[serde(default)]
+    interfaces: Vec<String>,
+    #[serde(default = "default_ipv6")]
+    ipv6: bool,
+}
+
+fn default_ipv6() -> bool {
+    true
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("linux_neighbors", build_linux_neighbors);
+}
+
+fn build_linux_neighbors(neighbors: LinuxNeighborsConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    Ok(Box::new(LinuxNeighbors::new(
+        neighbors.interfaces,
+        neighbors.ipv6,
+    )))
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::MikroTik;
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct MikroTikConfig {
+    ip: String,
+    user: String,
+    password: String,
+    ports: Vec<MikroTikPortConfig>,
+}
+
+#[derive(Deserialize)]
+struct MikroTikPortConfig {
+    interfaces: String,
+    location: String,
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("mikrotik", build_mikrotik);
+}
+
+fn build_mikrotik(mikrotik: MikroTikConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    Ok(Box::new(MikroTik::new(
+        &mikrotik.ip,
+        &mikrotik.user,
+        &mikrotik.password,
+        get_port_locations(&mikrotik),
+    )))
+}
+
+/// Maps every interface name of the comma separated lists to its location
+fn get_port_locations(mikrotik: &MikroTikConfig) -> HashMap<String, String> {
+    let mut ports = HashMap::new();
+
+    for port in mikrotik.ports.iter() {
+        for interface in port.interfaces.split(',') {
+            let interface = interface.trim();
+
+            if !interface.is_empty() {
+                ports.insert(interface.to_string(), port.location.to_string());
+            }
+        }
+    }
+
+    ports
+}
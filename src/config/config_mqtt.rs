@@ -12,9 +12,8 @@ pub struct MqttConfig {
 }
 
 pub(crate) fn build_mqtt(config: &AppConfig) -> MqttService {
-    if config.mqtt.is_some() {
-        let mqtt = config.mqtt.as_ref().unwrap();
-        return MqttService::new(&mqtt.host, &mqtt.user, &mqtt.password, &mqtt.topic)
+    if let Some(mqtt) = config.mqtt.as_ref() {
+        MqttService::new(&mqtt.host, &mqtt.user, &mqtt.password, &mqtt.topic)
     } else {
         panic!("Invalid MQTT Config...")
     }
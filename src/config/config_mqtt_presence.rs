@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{normalize_mac, MqttPresence, MqttPresenceField, MqttPresenceMapping};
+use crate::Pollable;
+
+/// Fields are written as `topic:<level>` or `payload:<field>[.<field>...]`
+#[derive(Deserialize)]
+pub struct MqttPresenceConfig {
+    topic: String,
+    mac: String,
+    location: String,
+    distance: Option<String>,
+    ip: Option<String>,
+    hostname: Option<String>,
+    user: Option<String>,
+    #[serde(default = "default_client_timeout")]
+    client_timeout: u64,
+    #[serde(default)]
+    devices: HashMap<String, String>,
+}
+
+fn default_client_timeout() -> u64 {
+    120
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("mqtt_presence", build_mqtt_presence);
+}
+
+fn build_mqtt_presence(presence: MqttPresenceConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let (Some(mac), Some(location)) = (parse_field(&presence.mac), parse_field(&presence.location)) else {
+        return Err(format!("invalid mapping for {}", presence.topic));
+    };
+
+    let mapping = MqttPresenceMapping {
+        mac,
+        location,
+        distance: presence.distance.as_deref().and_then(parse_field),
+        ip: presence.ip.as_deref().and_then(parse_field),
+        hostname: presence.hostname.as_deref().and_then(parse_field),
+        user: presence.user.as_deref().and_then(parse_field),
+        devices: presence.devices.iter()
+            .map(|(id, mac)| (id.to_string(), normalize_mac(mac).unwrap_or(mac.to_string())))
+            .collect(),
+    };
+
+    Ok(Box::new(MqttPresence::new(
+        context.mqtt.subscribe(&presence.topic),
+        mapping,
+        Duration::from_secs(presence.client_timeout),
+        context.updates.clone(),
+    )))
+}
+
+fn parse_field(field: &str) -> Option<MqttPresenceField> {
+    match field.split_once(':') {
+        Some(("topic", level)) => level.parse().ok().map(MqttPresenceField::Topic),
+        Some(("payload", path)) => Some(MqttPresenceField::Payload(path.split('.').map(String::from).collect())),
+        _ => {
+            println!("Invalid mqtt presence field {}, expected topic:<level> or payload:<field>", field);
+            None
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{normalize_mac, OmadaController, OmadaDevice};
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct OmadaControllerConfig {
+    url: String,
+    omadac_id: String,
+    /// The id of the site as used by the OpenAPI, not its name
+    site_id: String,
+    client_id: String,
+    client_secret: String,
+    devices: Vec<OmadaDeviceConfig>,
+}
+
+#[derive(Deserialize)]
+struct OmadaDeviceConfig {
+    mac: String,
+    location: String,
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("omada_controller", build_omada_controller);
+}
+
+fn build_omada_controller(controller: OmadaControllerConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    if controller.site_id.is_empty() {
+        return Err("no site_id configured".to_string());
+    }
+
+    let mut omada_devices: HashMap<String, OmadaDevice> = HashMap::with_capacity(controller.devices.len());
+
+    for device in controller.devices.iter() {
+        let mac = normalize_mac(&device.mac).ok_or_else(|| format!("invalid device mac {}", device.mac))?;
+        omada_devices.insert(mac, OmadaDevice {
+            location: device.location.to_string(),
+        });
+    }
+
+    Ok(Box::new(OmadaController::new(
+        &controller.url,
+        &controller.omadac_id,
+        &controller.site_id,
+        &controller.client_id,
+        &controller.client_secret,
+        omada_devices,
+    )))
+}
@@ -0,0 +1,47 @@
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{OpenWrt, OpenWrtLocation};
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct OpenWrtConfig {
+    ip: String,
+    #[serde(default = "default_user")]
+    user: String,
+    password: String,
+    locations: Vec<OpenWrtLocationConfig>,
+}
+
+#[derive(Deserialize)]
+struct OpenWrtLocationConfig {
+    device: Option<String>,
+    ssid: Option<String>,
+    location: String,
+}
+
+fn default_user() -> String {
+    "root".to_string()
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("openwrt", build_openwrt);
+}
+
+fn build_openwrt(openwrt: OpenWrtConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let locations = openwrt.locations.iter()
+        .filter(|location| location.device.is_some() || location.ssid.is_some())
+        .map(|location| OpenWrtLocation {
+            device: location.device.clone(),
+            ssid: location.ssid.clone(),
+            location: location.location.to_string(),
+        })
+        .collect();
+
+    Ok(Box::new(OpenWrt::new(
+        &openwrt.ip,
+        &openwrt.user,
+        &openwrt.password,
+        locations,
+    )))
+}
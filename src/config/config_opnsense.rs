@@ -1,8 +1,8 @@
 use serde::Deserialize;
 
-use crate::config::AppConfig;
+use crate::config::registry::{SourceContext, SourceRegistry};
 use crate::device::OPNSense;
-use crate::DeviceTypes;
+use crate::Pollable;
 
 #[derive(Deserialize)]
 pub struct OPNSenseConfig {
@@ -11,19 +11,14 @@ pub struct OPNSenseConfig {
     api_secret: String,
 }
 
-pub fn build_opnsense(config: &AppConfig, devices: &mut Vec<DeviceTypes>) {
-    if config.opnsense.is_some() {
-        let opnsense = config.opnsense.as_ref().unwrap();
-        devices.push(
-            DeviceTypes::OPNSense(
-                OPNSense::new(
-                    &opnsense.ip,
-                    &opnsense.api_key,
-                    &opnsense.api_secret,
-                )
-            )
-        );
-    } else {
-        println!("OPNsense not configured...skipping");
-    }
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("opnsense", build_opnsense);
+}
+
+fn build_opnsense(opnsense: OPNSenseConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    Ok(Box::new(OPNSense::new(
+        &opnsense.ip,
+        &opnsense.api_key,
+        &opnsense.api_secret,
+    )))
 }
\ No newline at end of file
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{normalize_mac, RadiusAccounting};
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct RadiusAccountingConfig {
+    #[serde(default = "default_bind")]
+    bind: String,
+    secret: String,
+    #[serde(default = "default_session_timeout")]
+    session_timeout: u64,
+    nas: Vec<RadiusNasConfig>,
+}
+
+/// A NAS is identified by its NAS-Identifier, the mac address of the Called-Station-Id or its NAS-IP-Address
+#[derive(Deserialize)]
+struct RadiusNasConfig {
+    id: String,
+    location: String,
+}
+
+fn default_bind() -> String {
+    "0.0.0.0:1813".to_string()
+}
+
+fn default_session_timeout() -> u64 {
+    3600
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("radius_accounting", build_radius_accounting);
+}
+
+fn build_radius_accounting(radius: RadiusAccountingConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let nas: HashMap<String, String> = radius.nas.iter()
+        .map(|nas| (normalize_mac(&nas.id).unwrap_or(nas.id.to_lowercase()), nas.location.to_string()))
+        .collect();
+
+    Ok(Box::new(RadiusAccounting::new(
+        &radius.bind,
+        &radius.secret,
+        nas,
+        Duration::from_secs(radius.session_timeout),
+        context.updates.clone(),
+    )))
+}
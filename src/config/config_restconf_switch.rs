@@ -0,0 +1,95 @@
+use serde::Deserialize;
+
+use crate::config::config_hp_switch::get_interface_list;
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{RestconfMapping, RestconfSwitch};
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct RestconfSwitchConfig {
+    ip: String,
+    user: String,
+    password: String,
+    ports: String,
+    location: String,
+    vlan: Option<u16>,
+    #[serde(default = "default_model")]
+    model: String,
+    mapping: Option<RestconfMappingConfig>,
+}
+
+/// A custom model, paths are separated by `/` and relative to the restconf root,
+/// the field paths are relative to a single entry
+#[derive(Deserialize)]
+struct RestconfMappingConfig {
+    path: String,
+    entries: String,
+    mac: String,
+    vlan: String,
+    interface: String,
+}
+
+fn default_model() -> String {
+    "openconfig".to_string()
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("restconf_switch", build_restconf_switch);
+}
+
+fn build_restconf_switch(switch: RestconfSwitchConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let mapping = match switch.mapping.as_ref() {
+        Some(mapping) => get_mapping(mapping),
+        None => get_model(&switch.model).ok_or_else(|| format!("unknown model {}", switch.model))?,
+    };
+
+    Ok(Box::new(RestconfSwitch::new(
+        &switch.ip,
+        &switch.user,
+        &switch.password,
+        get_interface_list(&switch.ports),
+        &switch.location,
+        switch.vlan,
+        mapping,
+    )))
+}
+
+/// Built-in mappings for common yang models
+fn get_model(name: &str) -> Option<RestconfMapping> {
+    let mapping = match name {
+        "openconfig" => RestconfMappingConfig {
+            path: "data/openconfig-network-instance:network-instances/network-instance=default/fdb/mac-table/entries".to_string(),
+            entries: "openconfig-network-instance:entries/entry".to_string(),
+            mac: "mac-address".to_string(),
+            vlan: "vlan".to_string(),
+            interface: "interface/interface-ref/state/interface".to_string(),
+        },
+        "cisco-ios-xe" => RestconfMappingConfig {
+            path: "data/Cisco-IOS-XE-matm-oper:matm-oper-data".to_string(),
+            entries: "Cisco-IOS-XE-matm-oper:matm-oper-data/matm-table/matm-mac-entry".to_string(),
+            mac: "mac".to_string(),
+            vlan: "vlan-id-number".to_string(),
+            interface: "port".to_string(),
+        },
+        _ => return None,
+    };
+
+    Some(get_mapping(&mapping))
+}
+
+fn get_mapping(mapping: &RestconfMappingConfig) -> RestconfMapping {
+    RestconfMapping {
+        path: mapping.path.to_string(),
+        entries: split_path(&mapping.entries),
+        mac: split_path(&mapping.mac),
+        vlan: split_path(&mapping.vlan),
+        interface: split_path(&mapping.interface),
+    }
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::config_hp_switch::get_port_list;
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{SnmpTrapReceiver, SnmpTrapSwitch};
+use crate::Pollable;
+
+#[derive(Deserialize)]
+pub struct SnmpTrapConfig {
+    #[serde(default = "default_bind")]
+    bind: String,
+    community: String,
+    #[serde(default = "default_client_timeout")]
+    client_timeout: u64,
+    switches: Vec<SnmpTrapSwitchConfig>,
+}
+
+/// Ports are bridge port numbers, written as ranges like the ports of the HP switches
+#[derive(Deserialize)]
+struct SnmpTrapSwitchConfig {
+    ip: String,
+    ports: String,
+    location: String,
+}
+
+fn default_bind() -> String {
+    "0.0.0.0:162".to_string()
+}
+
+fn default_client_timeout() -> u64 {
+    3600
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("snmp_traps", build_snmp_traps);
+}
+
+fn build_snmp_traps(snmp_traps: SnmpTrapConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let mut switches: HashMap<IpAddr, SnmpTrapSwitch> = HashMap::with_capacity(snmp_traps.switches.len());
+
+    for switch in snmp_traps.switches.iter() {
+        let Ok(ip) = switch.ip.parse::<IpAddr>() else {
+            println!("{} is not a valid IP Address", &switch.ip);
+            continue;
+        };
+
+        switches.insert(ip, SnmpTrapSwitch {
+            ports: get_port_list(&switch.ports),
+            location: switch.location.to_string(),
+        });
+    }
+
+    Ok(Box::new(SnmpTrapReceiver::new(
+        &snmp_traps.bind,
+        &snmp_traps.community,
+        switches,
+        Duration::from_secs(snmp_traps.client_timeout),
+        context.updates.clone(),
+        context.poll_triggers.clone(),
+    )))
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::config_hp_switch::get_interface_list;
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{SshSwitch, TextFsm};
+use crate::Pollable;
+
+/// `template` is the name of a built-in template, `template_file` a TextFSM template with
+/// the values `MAC`, `VLAN` and `PORT` (the ntc-templates names are understood as well).
+/// `host_key` is the SHA256 fingerprint of the switch as printed by `ssh-keygen -lf`, e.g. `SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8`
+#[derive(Deserialize)]
+pub struct SshSwitchConfig {
+    ip: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    user: String,
+    password: String,
+    host_key: String,
+    command: Option<String>,
+    #[serde(default = "default_template")]
+    template: String,
+    template_file: Option<String>,
+    ports: String,
+    location: String,
+    vlan: Option<u16>,
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+fn default_template() -> String {
+    "cisco-ios".to_string()
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+const CISCO_IOS_TEMPLATE: &str = r"Value VLAN (\d+)
+Value MAC ([0-9a-fA-F]{4}\.[0-9a-fA-F]{4}\.[0-9a-fA-F]{4})
+Value PORT (\S+)
+
+Start
+  ^\s*\*?\s*${VLAN}\s+${MAC}\s+\S+\s+(?:\S+\s+){0,3}${PORT}\s*$$ -> Record
+";
+
+const HP_PROCURVE_TEMPLATE: &str = r"Value MAC ([0-9a-fA-F]{6}-[0-9a-fA-F]{6})
+Value PORT (\S+)
+Value VLAN (\d+)
+
+Start
+  ^\s*${MAC}\s+${PORT}\s+${VLAN}\s*$$ -> Record
+";
+
+const ARUBA_CX_TEMPLATE: &str = r"Value MAC ([0-9a-fA-F]{2}(?::[0-9a-fA-F]{2}){5})
+Value VLAN (\d+)
+Value PORT (\S+)
+
+Start
+  ^\s*${MAC}\s+${VLAN}\s+\S+\s+${PORT}\s*$$ -> Record
+";
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("ssh_switch", build_ssh_switch);
+}
+
+fn build_ssh_switch(switch: SshSwitchConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let (template, command) = get_template(&switch)?;
+    let template = TextFsm::parse(&template).map_err(|error| format!("invalid template: {}", error))?;
+
+    let command = switch.command.clone()
+        .or(command)
+        .ok_or_else(|| format!("no command configured for {}", switch.ip))?;
+
+    let host_key = switch.host_key.strip_prefix("SHA256:").unwrap_or(&switch.host_key).trim_end_matches('=');
+    if host_key.is_empty() {
+        return Err(format!("no host_key configured for {}", switch.ip));
+    }
+
+    Ok(Box::new(SshSwitch::new(
+        &switch.ip,
+        switch.port,
+        &switch.user,
+        &switch.password,
+        host_key,
+        &command,
+        template,
+        get_interface_list(&switch.ports),
+        &switch.location,
+        switch.vlan,
+        Duration::from_secs(switch.timeout),
+    )))
+}
+
+/// Returns the template and, for built-in templates, the command producing the matching output
+fn get_template(switch: &SshSwitchConfig) -> Result<(String, Option<String>), String> {
+    if let Some(path) = switch.template_file.as_ref() {
+        return fs::read_to_string(path)
+            .map(|template| (template, None))
+            .map_err(|error| format!("could not read template {}: {}", path, error));
+    }
+
+    let (template, command) = match switch.template.as_str() {
+        "cisco-ios" => (CISCO_IOS_TEMPLATE, "show mac address-table"),
+        "hp-procurve" => (HP_PROCURVE_TEMPLATE, "show mac-address"),
+        "aruba-cx" => (ARUBA_CX_TEMPLATE, "show mac-address-table"),
+        _ => return Err(format!("unknown template {}", switch.template)),
+    };
+
+    Ok((template.to_string(), Some(command.to_string())))
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::{Syslog, SyslogRule};
+use crate::Pollable;
+
+const MAC_PATTERN: &str = r"[0-9a-fA-F]{2}(?:[:-][0-9a-fA-F]{2}){5}";
+const CISCO_MAC_PATTERN: &str = r"[0-9a-fA-F]{4}\.[0-9a-fA-F]{4}\.[0-9a-fA-F]{4}";
+
+#[derive(Deserialize)]
+pub struct SyslogConfig {
+    udp: Option<String>,
+    tcp: Option<String>,
+    #[serde(default = "default_client_timeout")]
+    client_timeout: u64,
+    #[serde(default)]
+    presets: Vec<String>,
+    #[serde(default)]
+    rules: Vec<SyslogRuleConfig>,
+    #[serde(default)]
+    locations: Vec<SyslogLocationConfig>,
+}
+
+#[derive(Deserialize)]
+struct SyslogRuleConfig {
+    pattern: String,
+    #[serde(default)]
+    remove: bool,
+    #[serde(default)]
+    remove_events: Vec<String>,
+    location: Option<String>,
+}
+
+/// Maps the captured `port` group, e.g. an interface name or AP mac address, of messages sent by `host` to a location.
+/// Without `host` the port is mapped for every sender that has no location of its own for it.
+#[derive(Deserialize)]
+struct SyslogLocationConfig {
+    host: Option<String>,
+    port: String,
+    location: String,
+}
+
+fn default_client_timeout() -> u64 {
+    3600
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("syslog", build_syslog);
+}
+
+fn build_syslog(syslog: SyslogConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let mut rules: Vec<SyslogRule> = Vec::new();
+
+    for preset in syslog.presets.iter() {
+        rules.extend(get_preset(preset).ok_or_else(|| format!("unknown syslog preset {}", preset))?);
+    }
+
+    for rule in syslog.rules.iter() {
+        let regex = Regex::new(&rule.pattern)
+            .map_err(|error| format!("invalid syslog pattern {}: {}", rule.pattern, error))?;
+
+        rules.push(SyslogRule {
+            regex,
+            remove: rule.remove,
+            remove_events: rule.remove_events.clone(),
+            location: rule.location.clone(),
+        });
+    }
+
+    let mut locations: HashMap<(Option<IpAddr>, String), String> = HashMap::new();
+    for location in syslog.locations.iter() {
+        let host = match location.host.as_ref().filter(|host| !host.is_empty()) {
+            Some(host) => Some(host.parse::<IpAddr>().map_err(|_| format!("invalid location host {}", host))?),
+            None => None,
+        };
+        locations.insert((host, location.port.to_string()), location.location.to_string());
+    }
+
+    Ok(Box::new(Syslog::new(
+        syslog.udp.as_ref(),
+        syslog.tcp.as_ref(),
+        rules,
+        locations,
+        Duration::from_secs(syslog.client_timeout),
+        context.updates.clone(),
+    )))
+}
+
+/// Built-in rules for common message formats
+fn get_preset(name: &str) -> Option<Vec<SyslogRule>> {
+    let rules = match name {
+        "hostapd" => vec![
+            (format!(r"(?P<port>[\w.-]+): AP-STA-(?P<event>CONNECTED|DISCONNECTED) (?P<mac>{})", MAC_PATTERN), vec!["DISCONNECTED"]),
+        ],
+        "unifi" => vec![
+            (format!(r"(?P<port>[\w.-]+): STA (?P<mac>{}) IEEE 802\.11: (?P<event>associated|disassociated)", MAC_PATTERN), vec!["disassociated"]),
+        ],
+        // a violation is logged when a device is plugged into a secured port, the port locates it
+        "port-security" => vec![
+            (format!(r"%PORT_SECURITY-2-PSECURE_VIOLATION: .*MAC address (?P<mac>{}) on port (?P<port>[\w/.:-]*\w)", CISCO_MAC_PATTERN), vec![]),
+        ],
+        "dnsmasq" => vec![
+            (format!(r"DHCPACK\([\w.-]+\) (?P<ip>[0-9.]+) (?P<mac>{})(?: (?P<hostname>\S+))?", MAC_PATTERN), vec![]),
+        ],
+        _ => return None,
+    };
+
+    Some(rules.into_iter().map(|(pattern, remove_events)| SyslogRule {
+        regex: Regex::new(&pattern).unwrap(),
+        remove: false,
+        remove_events: remove_events.into_iter().map(String::from).collect(),
+        location: None,
+    }).collect())
+}
@@ -1,15 +1,21 @@
 use std::collections::HashMap;
 use serde::Deserialize;
 
-use crate::config::{AppConfig, DeviceTypes};
+use crate::config::registry::{SourceContext, SourceRegistry};
 use crate::device::{UniFiAP, UniFiController};
+use crate::Pollable;
 
 #[derive(Deserialize)]
 pub struct UniFiControllerConfig {
     ip: String,
+    #[serde(default)]
     user: String,
+    #[serde(default)]
     password: String,
+    api_key: Option<String>,
     access_points: Vec<UniFiAPConfig>,
+    /// A single topic without wildcards, replies are published to `<command_topic>/reply`
+    command_topic: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -18,29 +24,40 @@ struct UniFiAPConfig {
     location: String,
 }
 
-pub fn build_unifi_controllers(config: &AppConfig, devices: &mut Vec<DeviceTypes>) {
-    if config.unifi_controller.is_some() {
-        let controller = config.unifi_controller.as_ref().unwrap();
-        let mut access_points: HashMap<String, UniFiAP> = HashMap::with_capacity(controller.access_points.len());
-
-        for ap in controller.access_points.iter() {
-            access_points.insert(ap.mac.to_string(), UniFiAP {
-                mac: ap.mac.to_string(),
-                location: ap.location.to_string(),
-            });
-        }
-
-        devices.push(
-            DeviceTypes::UniFiController(
-                UniFiController::new(
-                    &controller.ip,
-                    &controller.user,
-                    &controller.password,
-                    access_points,
-                )
-            )
-        );
-    } else {
-        println!("UniFi Controller not configured...skipping");
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("unifi_controller", build_unifi_controller);
+}
+
+fn build_unifi_controller(controller: UniFiControllerConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    if let Some(command_topic) = controller.command_topic.as_ref()
+        && command_topic.contains(['+', '#']) {
+        return Err(format!("command_topic {} must not contain wildcards, replies could not be published below it", command_topic));
+    }
+
+    let mut access_points: HashMap<String, UniFiAP> = HashMap::with_capacity(controller.access_points.len());
+
+    for ap in controller.access_points.iter() {
+        access_points.insert(ap.mac.to_string(), UniFiAP {
+            location: ap.location.to_string(),
+        });
     }
+
+    let unifi_controller = UniFiController::new(
+        &controller.ip,
+        &controller.user,
+        &controller.password,
+        controller.api_key.as_ref().filter(|api_key| !api_key.is_empty()),
+        access_points,
+    )?;
+
+    if let Some(command_topic) = controller.command_topic.as_ref() {
+        let commands = context.mqtt.subscribe(command_topic);
+        tokio::spawn(unifi_controller.clone().handle_commands(
+            commands,
+            context.mqtt.client(),
+            format!("{}/reply", command_topic),
+        ));
+    }
+
+    Ok(Box::new(unifi_controller))
 }
\ No newline at end of file
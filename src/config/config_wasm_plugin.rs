@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::registry::{SourceContext, SourceRegistry};
+use crate::device::WasmPlugin;
+use crate::Pollable;
+
+/// The plugin can only reach the hosts listed in `allowed_hosts`, either as `http://host:port` or a plain host
+/// for `https://host`, `config` is handed to it as json
+#[derive(Deserialize)]
+pub struct WasmPluginConfig {
+    path: String,
+    #[serde(default)]
+    config: serde_json::Value,
+    #[serde(default)]
+    allowed_hosts: Vec<String>,
+    #[serde(default = "default_memory_limit")]
+    memory_limit: usize,
+    #[serde(default = "default_fuel")]
+    fuel: u64,
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+/// Memory limit in MiB
+fn default_memory_limit() -> usize {
+    16
+}
+
+fn default_fuel() -> u64 {
+    1_000_000_000
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+pub fn register(registry: &mut SourceRegistry) {
+    registry.register("wasm_plugin", build_wasm_plugin);
+}
+
+fn build_wasm_plugin(plugin: WasmPluginConfig, _context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+    let wasm_plugin = WasmPlugin::new(
+        &plugin.path,
+        plugin.config.to_string(),
+        plugin.allowed_hosts,
+        plugin.memory_limit * 1024 * 1024,
+        plugin.fuel,
+        Duration::from_secs(plugin.timeout),
+    ).map_err(|error| format!("failed to load {}: {}", plugin.path, error))?;
+
+    Ok(Box::new(wasm_plugin))
+}
@@ -1,40 +1,262 @@
 use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
 
 use serde::Deserialize;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Notify;
 
-use config_hp_switch::HPSwitchConfig;
-
-use crate::config::config_opnsense::OPNSenseConfig;
-use crate::config::config_unifi_controller::UniFiControllerConfig;
+use crate::circuit_breaker::CircuitBreaker;
 use crate::config::config_mqtt::MqttConfig;
-use crate::DeviceTypes;
+use crate::config::registry::{SourceConfig, SourceContext, SourceRegistry};
 use crate::mqtt_service::MqttService;
+use crate::{Pollable, PollTriggers, Source, SourceUpdate, UpdateSender};
 
+mod registry;
 mod config_hp_switch;
 mod config_unifi_controller;
 mod config_opnsense;
 mod config_mqtt;
+mod config_omada_controller;
+mod config_mikrotik;
+mod config_openwrt;
+mod config_fritzbox;
+mod config_linux_neighbors;
+mod config_dhcp_leases;
+mod config_hostapd;
+mod config_radius;
+mod config_syslog;
+mod config_snmp_traps;
+mod config_mqtt_presence;
+mod config_external_command;
+mod config_wasm_plugin;
+mod config_restconf_switch;
+mod config_ssh_switch;
+
+/// Top-level keys of configurations written before `sources` and the source type they hold
+const LEGACY_SOURCE_KEYS: [(&str, &str); 18] = [
+    ("hp_switches", "hp_switch"),
+    ("unifi_controller", "unifi_controller"),
+    ("opnsense", "opnsense"),
+    ("omada_controller", "omada_controller"),
+    ("mikrotiks", "mikrotik"),
+    ("openwrts", "openwrt"),
+    ("fritzboxes", "fritzbox"),
+    ("linux_neighbors", "linux_neighbors"),
+    ("dhcp_lease_files", "dhcp_lease_file"),
+    ("hostapds", "hostapd"),
+    ("radius_accounting", "radius_accounting"),
+    ("syslog", "syslog"),
+    ("snmp_traps", "snmp_traps"),
+    ("mqtt_presence", "mqtt_presence"),
+    ("external_commands", "external_command"),
+    ("wasm_plugins", "wasm_plugin"),
+    ("restconf_switches", "restconf_switch"),
+    ("ssh_switches", "ssh_switch"),
+];
+
+/// Time a source with its own timeout gets to clean up, e.g. to kill a command, before the poll is abandoned
+const SOURCE_TIMEOUT_GRACE: Duration = Duration::from_secs(1);
 
 #[derive(Deserialize)]
 pub struct AppConfig {
-    hp_switches: Option<Vec<HPSwitchConfig>>,
-    unifi_controller: Option<UniFiControllerConfig>,
-    opnsense: Option<OPNSenseConfig>,
+    #[serde(default)]
+    sources: Vec<SourceConfig>,
     mqtt: Option<MqttConfig>,
+    /// The default time between two polls of a source, in seconds
     scan_interval: u64,
+    /// The default time a poll may take, in seconds, see `get_poll_timeout` for sources with their own timeout
+    #[serde(default = "default_poll_timeout")]
+    poll_timeout: u64,
+    /// How many sources are polled at the same time
+    #[serde(default = "default_max_concurrent_polls")]
+    max_concurrent_polls: usize,
+    /// How long the clients of a failing source are kept, in seconds
+    #[serde(default = "default_cache_ttl")]
+    cache_ttl: u64,
+    /// How often a failed poll is retried before the poll counts as failed
+    #[serde(default = "default_retries")]
+    retries: u32,
+    /// The delay before the first retry in milliseconds, doubled for every further retry
+    #[serde(default = "default_retry_backoff")]
+    retry_backoff: u64,
+    /// After this many failed polls in a row a source is not polled until `breaker_cooldown` has passed
+    #[serde(default = "default_breaker_threshold")]
+    breaker_threshold: u32,
+    /// How long an open circuit breaker pauses the polls of a source, in seconds
+    #[serde(default = "default_breaker_cooldown")]
+    breaker_cooldown: u64,
+}
+
+fn default_poll_timeout() -> u64 {
+    5
+}
+
+fn default_max_concurrent_polls() -> usize {
+    8
+}
+
+fn default_cache_ttl() -> u64 {
+    300
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff() -> u64 {
+    500
+}
+
+fn default_breaker_threshold() -> u32 {
+    3
 }
 
-pub fn build_config() -> (Vec<DeviceTypes>, MqttService, u64) {
+fn default_breaker_cooldown() -> u64 {
+    300
+}
+
+/// Every source type has to be registered here to be available in the configuration
+fn build_registry() -> SourceRegistry {
+    let mut registry = SourceRegistry::new();
+
+    config_hp_switch::register(&mut registry);
+    config_unifi_controller::register(&mut registry);
+    config_opnsense::register(&mut registry);
+    config_omada_controller::register(&mut registry);
+    config_mikrotik::register(&mut registry);
+    config_openwrt::register(&mut registry);
+    config_fritzbox::register(&mut registry);
+    config_linux_neighbors::register(&mut registry);
+    config_dhcp_leases::register(&mut registry);
+    config_hostapd::register(&mut registry);
+    config_radius::register(&mut registry);
+    config_syslog::register(&mut registry);
+    config_snmp_traps::register(&mut registry);
+    config_mqtt_presence::register(&mut registry);
+    config_external_command::register(&mut registry);
+    config_wasm_plugin::register(&mut registry);
+    config_restconf_switch::register(&mut registry);
+    config_ssh_switch::register(&mut registry);
+
+    registry
+}
+
+pub fn build_config(poll_triggers: &PollTriggers, update_sender: &UnboundedSender<(String, SourceUpdate)>) -> (Vec<Source>, MqttService, usize) {
     let config_json = fs::read_to_string("config.json").unwrap();
-    let config: AppConfig = serde_json::from_str(&config_json).unwrap();
+    let mut config: serde_json::Value = serde_json::from_str(&config_json).unwrap();
+    migrate_legacy_sources(&mut config);
+    let config: AppConfig = serde_json::from_value(config).unwrap();
+
+    let mut mqtt = config_mqtt::build_mqtt(&config);
+    let registry = build_registry();
+
+    let mut sources: Vec<Source> = Vec::with_capacity(config.sources.len());
+    for source in config.sources {
+        let name = get_source_name(&source, &sources);
+        if sources.iter().any(|existing| existing.name == name) {
+            println!("Source name {} is used more than once...skipping", name);
+            continue;
+        }
+
+        let cache_ttl = Duration::from_secs(source.cache_ttl.unwrap_or(config.cache_ttl));
+        let poll_interval = Duration::from_secs(source.poll_interval.unwrap_or(config.scan_interval));
+        let poll_timeout = Duration::from_secs(source.poll_timeout.unwrap_or(config.poll_timeout));
+        let mut context = SourceContext {
+            mqtt: &mut mqtt,
+            poll_triggers,
+            updates: UpdateSender {
+                source: name.to_string(),
+                sender: update_sender.clone(),
+            },
+        };
+
+        match registry.build(source, &mut context) {
+            Ok(device) => sources.push(Source {
+                poll_trigger: get_poll_trigger(device.as_ref(), poll_triggers),
+                poll_timeout: get_poll_timeout(device.as_ref(), poll_timeout),
+                name,
+                device,
+                cache_ttl,
+                poll_interval,
+                retries: config.retries,
+                retry_backoff: Duration::from_millis(config.retry_backoff),
+                breaker: CircuitBreaker::new(config.breaker_threshold, Duration::from_secs(config.breaker_cooldown)),
+            }),
+            Err(error) => println!("{}: {}...skipping", name, error),
+        }
+    }
 
-    let mut devices: Vec<DeviceTypes> = Vec::new();
+    if sources.is_empty() {
+        println!("No sources configured");
+    }
 
-    config_opnsense::build_opnsense(&config, &mut devices);
-    config_hp_switch::build_hp_switches(&config, &mut devices);
-    config_unifi_controller::build_unifi_controllers(&config, &mut devices);
+    (sources, mqtt, config.max_concurrent_polls)
+}
+
+/// Moves sources configured under the old top-level keys into `sources`, so they are not silently ignored
+fn migrate_legacy_sources(config: &mut serde_json::Value) {
+    let Some(config) = config.as_object_mut() else {
+        return;
+    };
+
+    let mut migrated = Vec::new();
+    for (key, source_type) in LEGACY_SOURCE_KEYS {
+        let entries = match config.remove(key) {
+            Some(serde_json::Value::Array(entries)) => entries,
+            Some(serde_json::Value::Null) | None => continue,
+            Some(entry) => vec![entry],
+        };
 
-    let mqtt = config_mqtt::build_mqtt(&config);
+        println!("{} is deprecated, moving {} {} source(s) to sources", key, entries.len(), source_type);
+        for mut entry in entries {
+            if let Some(entry) = entry.as_object_mut() {
+                entry.insert("type".to_string(), source_type.into());
+            }
+            migrated.push(entry);
+        }
+    }
 
-    return (devices, mqtt, config.scan_interval);
-}
\ No newline at end of file
+    if migrated.is_empty() {
+        return;
+    }
+
+    match config.entry("sources").or_insert_with(|| serde_json::Value::Array(Vec::new())) {
+        serde_json::Value::Array(sources) => sources.extend(migrated),
+        _ => panic!("sources has to be a list"),
+    }
+}
+
+/// Sources with their own `timeout` (ssh_switch, external_command, wasm_plugin) keep it,
+/// `poll_timeout` only wins if it is longer than that timeout plus a grace period
+fn get_poll_timeout(device: &dyn Pollable, poll_timeout: Duration) -> Duration {
+    match device.timeout() {
+        Some(timeout) => poll_timeout.max(timeout + SOURCE_TIMEOUT_GRACE),
+        None => poll_timeout,
+    }
+}
+
+fn get_poll_trigger(device: &dyn Pollable, poll_triggers: &PollTriggers) -> Arc<Notify> {
+    let poll_trigger = Arc::new(Notify::new());
+    if let Some(address) = device.address() {
+        poll_triggers.add(address, poll_trigger.clone());
+    }
+
+    poll_trigger
+}
+
+/// Sources without a name are named after their type, numbered if the type is used more than once
+fn get_source_name(source: &SourceConfig, sources: &[Source]) -> String {
+    if let Some(name) = source.name.as_ref() {
+        return name.to_string();
+    }
+
+    let mut name = source.source_type.to_string();
+    let mut number = 1;
+    while sources.iter().any(|existing| existing.name == name) {
+        number += 1;
+        name = format!("{}-{}", source.source_type, number);
+    }
+
+    name
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::mqtt_service::MqttService;
+use crate::{Pollable, PollTriggers, UpdateSender};
+
+/// Shared services a source may use while it is built, `updates` belongs to the source being built
+pub struct SourceContext<'a> {
+    pub mqtt: &'a mut MqttService,
+    pub poll_triggers: &'a PollTriggers,
+    pub updates: UpdateSender,
+}
+
+pub type SourceFactory<C> = fn(C, &mut SourceContext) -> Result<Box<dyn Pollable>, String>;
+
+type SourceBuilder = Box<dyn Fn(serde_json::Value, &mut SourceContext) -> Result<Box<dyn Pollable>, String>>;
+
+/// A configured source, `type` selects the registered source type, all other fields are its configuration
+#[derive(Deserialize)]
+pub struct SourceConfig {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub name: Option<String>,
+    /// Overrides the global `cache_ttl` for this source
+    pub cache_ttl: Option<u64>,
+    /// Overrides the global `scan_interval` for this source
+    pub poll_interval: Option<u64>,
+    /// Overrides the global `poll_timeout` for this source, a longer `timeout` of the source itself wins
+    pub poll_timeout: Option<u64>,
+    #[serde(flatten)]
+    config: serde_json::Value,
+}
+
+/// Maps the source types to the deserializer of their configuration and their factory
+pub struct SourceRegistry {
+    builders: HashMap<&'static str, SourceBuilder>,
+}
+
+impl SourceRegistry {
+    pub fn new() -> SourceRegistry {
+        SourceRegistry {
+            builders: HashMap::new(),
+        }
+    }
+
+    pub fn register<C: DeserializeOwned + 'static>(&mut self, source_type: &'static str, factory: SourceFactory<C>) {
+        let builder: SourceBuilder = Box::new(move |config, context| {
+            let config: C = serde_json::from_value(config).map_err(|error| format!("invalid configuration: {}", error))?;
+            factory(config, context)
+        });
+
+        if self.builders.insert(source_type, builder).is_some() {
+            panic!("Source type {} registered twice", source_type);
+        }
+    }
+
+    pub fn build(&self, source: SourceConfig, context: &mut SourceContext) -> Result<Box<dyn Pollable>, String> {
+        let SourceConfig { source_type, config, .. } = source;
+
+        match self.builders.get(source_type.as_str()) {
+            Some(builder) => builder(config, context).map_err(|error| format!("{}: {}", source_type, error)),
+            None => Err(format!("unknown source type {}", source_type)),
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::fs;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time;
+
+use crate::{DeviceLocation, Pollable, UpdateSender};
+use crate::device::{DhcpLeaseFile, DhcpLeaseFormat};
+use crate::error::SourceError;
+
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+struct Lease {
+    mac: String,
+    ip: String,
+    hostname: String,
+    expires: Option<u64>,
+}
+
+#[async_trait]
+impl Pollable for DhcpLeaseFile {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let content = fs::read_to_string(&self.path).await
+            .map_err(|error| SourceError::Io(format!("failed to read lease file {}: {}", self.path.display(), error)))?;
+
+        Ok(read_leases(&content, self.format))
+    }
+}
+
+impl DhcpLeaseFile {
+    pub fn new(path: &str, format: DhcpLeaseFormat, updates: UpdateSender) -> DhcpLeaseFile {
+        let path = PathBuf::from(path);
+        let watcher = watch_lease_file(&path, format, updates);
+
+        DhcpLeaseFile {
+            path,
+            format,
+            _watcher: watcher,
+        }
+    }
+}
+
+/// Returns the active leases of the file content, the newest IPv4 lease wins for every mac address
+/// while the addresses of IPv6 leases are collected
+fn read_leases(content: &str, format: DhcpLeaseFormat) -> Vec<DeviceLocation> {
+    let leases = match format {
+        DhcpLeaseFormat::Dnsmasq => parse_dnsmasq(content),
+        DhcpLeaseFormat::Isc => parse_isc(content),
+        DhcpLeaseFormat::Kea => parse_kea(content),
+    };
+
+    let now = unix_time();
+    let mut clients: HashMap<String, DeviceLocation> = HashMap::new();
+    for lease in leases.into_iter().filter(|lease| lease.expires.is_none_or(|expires| expires > now)) {
+        let client = clients.entry(lease.mac.to_string()).or_insert_with(|| DeviceLocation {
+            device_mac: lease.mac,
+            ..Default::default()
+        });
+
+        match lease.ip.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => client.ipv6.push(lease.ip),
+            _ => client.ipv4 = lease.ip,
+        }
+        if !lease.hostname.is_empty() {
+            client.hostname = lease.hostname;
+        }
+        client.lease_expires = lease.expires;
+    }
+
+    clients.into_values().collect()
+}
+
+/// Watches the directory of the lease file, since most servers replace the file instead of writing to it.
+/// Changes are applied by `apply_lease_changes` once the file is no longer being written.
+fn watch_lease_file(path: &Path, format: DhcpLeaseFormat, updates: UpdateSender) -> Option<RecommendedWatcher> {
+    let file_name = path.file_name()?.to_os_string();
+    let directory = path.parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let (changes, changes_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(apply_lease_changes(changes_receiver, path.to_path_buf(), format, updates));
+
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event
+            && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && event.paths.iter().any(|path| path.file_name() == Some(file_name.as_os_str())) {
+            let _ = changes.send(());
+        }
+    });
+
+    let result = watcher.and_then(|mut watcher| {
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    });
+
+    match result {
+        Ok(watcher) => Some(watcher),
+        Err(error) => {
+            println!("failed to watch lease file {}, changes are only picked up when polling: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Pushes the current leases and removes the clients whose lease disappeared from the file.
+/// The file is read once no change was seen for `SETTLE_TIME` and only if its last line is complete,
+/// a half-written file would remove every client whose lease was not written yet.
+async fn apply_lease_changes(mut changes: UnboundedReceiver<()>, path: PathBuf, format: DhcpLeaseFormat, updates: UpdateSender) {
+    let mut known_macs: HashSet<String> = HashSet::new();
+
+    while changes.recv().await.is_some() {
+        while let Ok(Some(())) = time::timeout(SETTLE_TIME, changes.recv()).await {}
+
+        let Ok(content) = fs::read_to_string(&path).await else {
+            continue;
+        };
+        if !content.is_empty() && !content.ends_with('\n') {
+            continue;
+        }
+
+        let clients = read_leases(&content, format);
+        let current_macs: HashSet<String> = clients.iter().map(|client| client.device_mac.to_string()).collect();
+
+        for mac in known_macs.difference(&current_macs) {
+            updates.remove(mac);
+        }
+        for client in clients {
+            updates.upsert(client);
+        }
+        known_macs = current_macs;
+    }
+}
+
+/// `<expiry> <mac> <ip> <hostname> <client-id>`, IPv6 leases carry a DUID instead of a mac address and are skipped
+fn parse_dnsmasq(content: &str) -> Vec<Lease> {
+    content.lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() < 4 || !is_mac(columns[1]) {
+                return None;
+            }
+
+            let expires = columns[0].parse::<u64>().ok()?;
+            Some(Lease {
+                mac: columns[1].to_lowercase(),
+                ip: columns[2].to_string(),
+                hostname: if columns[3] == "*" { String::new() } else { columns[3].to_string() },
+                expires: if expires == 0 { None } else { Some(expires) },
+            })
+        })
+        .collect()
+}
+
+/// Parses the `lease <ip> { ... }` blocks of an ISC dhcpd.leases file, later blocks supersede earlier ones
+fn parse_isc(content: &str) -> Vec<Lease> {
+    let mut leases: HashMap<String, Lease> = HashMap::new();
+    let mut current: Option<(Lease, bool)> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if let Some(ip) = line.strip_prefix("lease ").and_then(|rest| rest.strip_suffix('{')) {
+            current = Some((Lease {
+                mac: String::new(),
+                ip: ip.trim().to_string(),
+                hostname: String::new(),
+                expires: None,
+            }, false));
+            continue;
+        }
+
+        let Some((lease, active)) = current.as_mut() else {
+            continue;
+        };
+
+        if line == "}" {
+            let (lease, active) = current.take().unwrap();
+            if active && !lease.mac.is_empty() {
+                leases.insert(lease.ip.to_string(), lease);
+            } else {
+                leases.remove(&lease.ip);
+            }
+        } else if let Some(state) = line.strip_prefix("binding state ") {
+            *active = state.trim_end_matches(';') == "active";
+        } else if let Some(mac) = line.strip_prefix("hardware ethernet ") {
+            lease.mac = mac.trim_end_matches(';').to_lowercase();
+        } else if let Some(hostname) = line.strip_prefix("client-hostname ") {
+            lease.hostname = hostname.trim_end_matches(';').trim_matches('"').to_string();
+        } else if let Some(ends) = line.strip_prefix("ends ") {
+            lease.expires = parse_isc_date(ends.trim_end_matches(';'));
+        }
+    }
+
+    leases.into_values().collect()
+}
+
+/// Kea memfile leases are CSV files with a header, the columns differ between the v4 and v6 files
+fn parse_kea(content: &str) -> Vec<Lease> {
+    let mut lines = content.lines();
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+
+    let columns: HashMap<&str, usize> = header.split(',')
+        .enumerate()
+        .map(|(index, column)| (column.trim(), index))
+        .collect();
+
+    let (Some(&address), Some(&hwaddr), Some(&expire)) = (columns.get("address"), columns.get("hwaddr"), columns.get("expire")) else {
+        println!("kea lease file is missing the address, hwaddr or expire column");
+        return Vec::new();
+    };
+    let hostname = columns.get("hostname").copied();
+    let state = columns.get("state").copied();
+    let lease_type = columns.get("lease_type").copied();
+
+    let mut leases: HashMap<String, Lease> = HashMap::new();
+    for line in lines {
+        let values: Vec<&str> = line.split(',').collect();
+        let value = |index: usize| values.get(index).copied().unwrap_or_default();
+
+        let ip = value(address).to_string();
+        // 0 is the default state, declined and expired-reclaimed leases are no longer in use
+        // v6 files also contain delegated prefixes (lease type 2), which are not addresses of the client
+        if state.is_some_and(|state| value(state) != "0") || lease_type.is_some_and(|lease_type| value(lease_type) == "2") || !is_mac(value(hwaddr)) {
+            leases.remove(&ip);
+            continue;
+        }
+
+        leases.insert(ip.to_string(), Lease {
+            mac: value(hwaddr).to_lowercase(),
+            ip,
+            hostname: hostname.map(|hostname| value(hostname).to_string()).unwrap_or_default(),
+            expires: value(expire).parse().ok(),
+        });
+    }
+
+    leases.into_values().collect()
+}
+
+/// ISC dates are either `never`, `epoch <seconds>` or `<weekday> <yyyy>/<mm>/<dd> <hh>:<mm>:<ss>` in UTC
+fn parse_isc_date(date: &str) -> Option<u64> {
+    let columns: Vec<&str> = date.split_whitespace().collect();
+
+    match columns.as_slice() {
+        ["epoch", seconds, ..] => seconds.parse().ok(),
+        [_, date, time, ..] => {
+            let date: Vec<i64> = date.split('/').filter_map(|value| value.parse().ok()).collect();
+            let time: Vec<u64> = time.split(':').filter_map(|value| value.parse().ok()).collect();
+            if date.len() != 3 || time.len() != 3 {
+                return None;
+            }
+
+            let days = days_from_civil(date[0], date[1], date[2]);
+            Some(u64::try_from(days).ok()? * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+        }
+        _ => None,
+    }
+}
+
+/// Days since 1970-01-01 for a date of the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn is_mac(value: &str) -> bool {
+    value.len() == 17 && value.split(':').count() == 6
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut leases: Vec<Lease>) -> Vec<Lease> {
+        leases.sort_by(|a, b| a.ip.cmp(&b.ip));
+        leases
+    }
+
+    #[test]
+    fn parses_dnsmasq_leases() {
+        let content = "1760870000 00:11:22:33:44:55 192.168.1.10 laptop 01:00:11:22:33:44:55
+0 AA:BB:CC:DD:EE:FF 192.168.1.11 * *
+1760870000 1234567 2001:db8::10 phone 00:01:00:01:2c:1f:2e:3d:00:11:22:33:44:66
+duid 00:01:00:01:2c:1f:2e:3d:00:11:22:33:44:77
+";
+        let leases = sorted(parse_dnsmasq(content));
+
+        assert_eq!(leases.len(), 2);
+        assert_eq!(leases[0].mac, "00:11:22:33:44:55");
+        assert_eq!(leases[0].hostname, "laptop");
+        assert_eq!(leases[0].expires, Some(1760870000));
+        assert_eq!(leases[1].mac, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(leases[1].hostname, "");
+        assert_eq!(leases[1].expires, None);
+    }
+
+    #[test]
+    fn skips_truncated_dnsmasq_lines() {
+        let leases = parse_dnsmasq("1760870000 00:11:22:33:44:55 192.168.1.10 laptop *\n1760870000 00:11:22:33:4");
+
+        assert_eq!(leases.len(), 1);
+        assert!(parse_dnsmasq("not a lease file\n\n").is_empty());
+    }
+
+    #[test]
+    fn parses_isc_leases() {
+        let content = r#"# The format of this file is documented in the dhcpd.leases(5) manual page.
+lease 192.168.1.10 {
+  starts 0 2026/10/19 08:00:00;
+  ends 0 2026/10/19 20:00:00;
+  binding state active;
+  next binding state free;
+  hardware ethernet 00:11:22:33:44:55;
+  client-hostname "laptop";
+}
+lease 192.168.1.11 {
+  ends epoch 1760870000;
+  binding state active;
+  hardware ethernet AA:BB:CC:DD:EE:FF;
+}
+lease 192.168.1.10 {
+  ends 0 2026/10/20 08:00:00;
+  binding state active;
+  hardware ethernet 00:11:22:33:44:55;
+  client-hostname "laptop";
+}
+lease 192.168.1.11 {
+  binding state free;
+  hardware ethernet aa:bb:cc:dd:ee:ff;
+}
+"#;
+        let leases = parse_isc(content);
+
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].ip, "192.168.1.10");
+        assert_eq!(leases[0].mac, "00:11:22:33:44:55");
+        assert_eq!(leases[0].hostname, "laptop");
+        assert_eq!(leases[0].expires, Some(1792483200));
+    }
+
+    #[test]
+    fn ignores_unterminated_isc_lease() {
+        let content = "lease 192.168.1.10 {\n  binding state active;\n  hardware ethernet 00:11:22:33:44:55;\n}\nlease 192.168.1.11 {\n  binding state active;\n  hardware ether";
+        let leases = parse_isc(content);
+
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].ip, "192.168.1.10");
+    }
+
+    #[test]
+    fn parses_isc_dates() {
+        assert_eq!(parse_isc_date("epoch 1760870000"), Some(1760870000));
+        assert_eq!(parse_isc_date("4 1970/01/01 00:00:10"), Some(10));
+        assert_eq!(parse_isc_date("0 2026/10/18 12:30:00"), Some(1792326600));
+        assert_eq!(parse_isc_date("never"), None);
+        assert_eq!(parse_isc_date("0 2026/10 12:30:00"), None);
+    }
+
+    #[test]
+    fn parses_kea_leases() {
+        let content = "address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state,user_context
+192.168.1.10,00:11:22:33:44:55,,3600,1760870000,1,0,0,laptop,0,
+192.168.1.11,aa:bb:cc:dd:ee:ff,,3600,1760870000,1,0,0,printer,0,
+192.168.1.11,aa:bb:cc:dd:ee:ff,,0,1760866400,1,0,0,printer,2,
+192.168.1.12,,,3600,1760870000,1,0,0,,0,
+";
+        let leases = parse_kea(content);
+
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].ip, "192.168.1.10");
+        assert_eq!(leases[0].hostname, "laptop");
+        assert_eq!(leases[0].expires, Some(1760870000));
+    }
+
+    #[test]
+    fn handles_incomplete_kea_files() {
+        assert!(parse_kea("").is_empty());
+        assert!(parse_kea("address,client_id\n192.168.1.10,\n").is_empty());
+
+        let leases = parse_kea("address,hwaddr,expire,hostname,state\n192.168.1.10,00:11:22:33:44:55,1760870000,laptop,0\n192.168.1.11,aa:bb");
+        assert_eq!(leases.len(), 1);
+    }
+
+    #[test]
+    fn reads_kea_v6_leases_as_ipv6() {
+        let now = unix_time();
+        let content = format!("address,duid,valid_lifetime,expire,subnet_id,pref_lifetime,lease_type,iaid,prefix_len,fqdn_fwd,fqdn_rev,hostname,hwaddr,state,user_context
+2001:db8::10,00:01:00:01:2c:1f:2e:3d:00:11:22:33:44:55,3600,{0},1,1800,0,1,128,0,0,laptop,00:11:22:33:44:55,0,
+2001:db8::11,00:01:00:01:2c:1f:2e:3d:00:11:22:33:44:55,3600,{0},1,1800,0,2,128,0,0,,00:11:22:33:44:55,0,
+2001:db8:1::,00:01:00:01:2c:1f:2e:3d:00:11:22:33:44:55,3600,{0},2,1800,2,3,56,0,0,,00:11:22:33:44:55,0,
+2001:db8::12,00:03:00:01:aa:bb:cc:dd:ee:ff,3600,{0},1,1800,0,1,128,0,0,,,0,
+", now + 3600);
+        let clients = read_leases(&content, DhcpLeaseFormat::Kea);
+
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].device_mac, "00:11:22:33:44:55");
+        assert_eq!(clients[0].ipv4, "");
+        let mut ipv6 = clients[0].ipv6.clone();
+        ipv6.sort();
+        assert_eq!(ipv6, vec!["2001:db8::10", "2001:db8::11"]);
+        assert_eq!(clients[0].hostname, "laptop");
+    }
+
+    #[test]
+    fn drops_expired_leases() {
+        let now = unix_time();
+        let content = format!("{} 00:11:22:33:44:55 192.168.1.10 laptop *\n{} aa:bb:cc:dd:ee:ff 192.168.1.11 old *\n0 00:11:22:33:44:66 192.168.1.12 static *\n", now + 3600, now - 1);
+        let mut clients = read_leases(&content, DhcpLeaseFormat::Dnsmasq);
+        clients.sort_by(|a, b| a.ipv4.cmp(&b.ipv4));
+
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].device_mac, "00:11:22:33:44:55");
+        assert_eq!(clients[0].lease_expires, Some(now + 3600));
+        assert_eq!(clients[1].device_mac, "00:11:22:33:44:66");
+    }
+}
@@ -0,0 +1,102 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{self, AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::time;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::ExternalCommand;
+use crate::error::SourceError;
+
+const MAX_STDOUT_LENGTH: u64 = 16 * 1024 * 1024;
+const MAX_STDERR_LENGTH: u64 = 64 * 1024;
+
+#[async_trait]
+impl Pollable for ExternalCommand {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+
+        let mut child = child.map_err(|error| SourceError::Io(format!("failed to start {}: {}", self.command, error)))?;
+        let (Some(mut stdout_pipe), Some(mut stderr_pipe)) = (child.stdout.take(), child.stderr.take()) else {
+            return Err(SourceError::Io(format!("failed to open the output of {}", self.command)));
+        };
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        // dropping the future on timeout kills the process, the stderr read so far is kept
+        let result = time::timeout(self.timeout, async {
+            let (stdout_complete, _) = tokio::try_join!(
+                read_limited(&mut stdout_pipe, &mut stdout, MAX_STDOUT_LENGTH),
+                read_limited(&mut stderr_pipe, &mut stderr, MAX_STDERR_LENGTH),
+            )?;
+            Ok::<_, io::Error>((stdout_complete, child.wait().await?))
+        }).await;
+
+        let stderr = String::from_utf8_lossy(&stderr);
+        let Ok(result) = result else {
+            let mut error = format!("command {} timed out after {:?}", self.command, self.timeout);
+            if !stderr.trim().is_empty() {
+                error.push_str(&format!(", stderr: {}", stderr.trim()));
+            }
+            return Err(SourceError::Io(error));
+        };
+        let (stdout_complete, status) = result?;
+
+        for line in stderr.lines().filter(|line| !line.trim().is_empty()) {
+            println!("command {}: {}", self.command, line);
+        }
+
+        // the output of a failed command is likely incomplete, the cached clients are kept instead
+        if !status.success() {
+            return Err(SourceError::Io(format!("command {} exited with {}", self.command, status)));
+        }
+        if !stdout_complete {
+            return Err(SourceError::Io(format!("output of command {} exceeds {} bytes", self.command, MAX_STDOUT_LENGTH)));
+        }
+
+        Ok(String::from_utf8_lossy(&stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str::<DeviceLocation>(line) {
+                Ok(client) if !client.device_mac.is_empty() => Some(client),
+                Ok(_) => {
+                    println!("command {}: ignoring line without device_mac", self.command);
+                    None
+                }
+                Err(error) => {
+                    println!("command {}: ignoring invalid line: {}", self.command, error);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+}
+
+impl ExternalCommand {
+    pub fn new(command: &str, args: Vec<String>, timeout: Duration) -> ExternalCommand {
+        ExternalCommand {
+            command: command.to_string(),
+            args,
+            timeout,
+        }
+    }
+}
+
+/// Reads up to `limit` bytes into `buffer` and discards the rest, so the command never blocks on a full pipe.
+/// Returns whether everything fit into the buffer.
+async fn read_limited(pipe: &mut (impl AsyncRead + Unpin), buffer: &mut Vec<u8>, limit: u64) -> io::Result<bool> {
+    (&mut *pipe).take(limit).read_to_end(buffer).await?;
+    Ok(io::copy(pipe, &mut io::sink()).await? == 0)
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use md5::{Digest, Md5};
+use regex::Regex;
+use reqwest::StatusCode;
+use reqwest::header::WWW_AUTHENTICATE;
+use serde::Deserialize;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::FritzBox;
+use crate::error::SourceError;
+
+const HOSTS_SERVICE: &str = "urn:dslforum-org:service:Hosts:1";
+const MESH_ROLES: [&str; 2] = ["master", "slave"];
+
+struct HostEntry {
+    mac: String,
+    ip: String,
+    hostname: String,
+    /// How the host is connected, e.g. `Ethernet`, `802.11` or `HomePlug`
+    interface_type: String,
+}
+
+#[derive(Deserialize)]
+struct MeshList {
+    nodes: Vec<MeshNode>,
+}
+
+#[derive(Deserialize)]
+struct MeshNode {
+    uid: String,
+    device_name: String,
+    device_mac_address: String,
+    mesh_role: String,
+    #[serde(default)]
+    node_interfaces: Vec<MeshNodeInterface>,
+}
+
+#[derive(Deserialize)]
+struct MeshNodeInterface {
+    #[serde(default)]
+    node_links: Vec<MeshNodeLink>,
+}
+
+#[derive(Deserialize)]
+struct MeshNodeLink {
+    state: String,
+    node_1_uid: String,
+    node_2_uid: String,
+}
+
+#[async_trait]
+impl Pollable for FritzBox {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let hosts = self.get_hosts().await?;
+
+        let mesh_nodes = match self.get_mesh_nodes().await {
+            Ok(mesh_nodes) => mesh_nodes,
+            Err(error) => {
+                println!("fritzbox {}: failed to read mesh topology, assigning every host to the fritzbox: {}", self.ip, error);
+                HashMap::new()
+            }
+        };
+
+        Ok(hosts.into_iter().map(|host| {
+            let (remote_mac, remote_ip, location) = match mesh_nodes.get(&host.mac) {
+                Some((node_name, node_mac)) => (
+                    node_mac.to_string(),
+                    String::new(),
+                    self.get_node_location(node_name, node_mac),
+                ),
+                None => (String::new(), self.ip.to_string(), self.get_interface_location(&host.interface_type)),
+            };
+
+            DeviceLocation {
+                ipv4: host.ip,
+                device_mac: host.mac,
+                hostname: host.hostname,
+                remote_ip,
+                remote_mac,
+                location,
+                ..Default::default()
+            }
+        }).collect())
+    }
+}
+
+impl FritzBox {
+    pub fn new(ip: &str, user: &str, password: &str, location: &str, mesh_nodes: HashMap<String, String>, interface_locations: HashMap<String, String>) -> FritzBox {
+        let client = reqwest::Client::builder()
+            .build()
+            .unwrap();
+
+        FritzBox {
+            ip: ip.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+            location: location.to_string(),
+            base_url: format!("http://{}:49000", ip),
+            control_url: "/upnp/control/hosts".to_string(),
+            mesh_nodes,
+            interface_locations,
+            client,
+        }
+    }
+
+    /// Reads all active hosts from the host list file the fritzbox generates on request
+    async fn get_hosts(&self) -> Result<Vec<HostEntry>, SourceError> {
+        let response = self.soap_call("X_AVM-DE_GetHostListPath").await?;
+        let path = extract_tag(&response, "NewX_AVM-DE_HostListPath")
+            .ok_or(SourceError::Parse("host list path missing in response".to_string()))?;
+
+        let host_list = self.get(&path).await?;
+        let item_regex = Regex::new(r"(?s)<Item>(.*?)</Item>").unwrap();
+
+        Ok(item_regex.captures_iter(&host_list)
+            .map(|item| item[1].to_string())
+            .filter(|item| extract_tag(item, "Active").as_deref() == Some("1"))
+            .filter_map(|item| Some(HostEntry {
+                mac: extract_tag(&item, "MACAddress").filter(|mac| !mac.is_empty())?.to_lowercase(),
+                ip: extract_tag(&item, "IPAddress").unwrap_or_default(),
+                hostname: extract_tag(&item, "HostName").unwrap_or_default(),
+                interface_type: extract_tag(&item, "InterfaceType").unwrap_or_default(),
+            }))
+            .collect())
+    }
+
+    /// Maps the mac address of every client to the name and mac address of the mesh node it is connected to
+    async fn get_mesh_nodes(&self) -> Result<HashMap<String, (String, String)>, SourceError> {
+        let response = self.soap_call("X_AVM-DE_GetMeshListPath").await?;
+        let path = extract_tag(&response, "NewX_AVM-DE_MeshListPath")
+            .ok_or(SourceError::Parse("mesh list path missing in response".to_string()))?;
+
+        let mesh_list: MeshList = serde_json::from_str(&self.get(&path).await?)?;
+
+        let mesh_devices: HashMap<&str, &MeshNode> = mesh_list.nodes.iter()
+            .filter(|node| MESH_ROLES.contains(&node.mesh_role.as_str()))
+            .map(|node| (node.uid.as_str(), node))
+            .collect();
+
+        let mut clients = HashMap::new();
+        for node in mesh_list.nodes.iter().filter(|node| !mesh_devices.contains_key(node.uid.as_str())) {
+            let mesh_device = node.node_interfaces.iter()
+                .flat_map(|interface| interface.node_links.iter())
+                .filter(|link| link.state == "CONNECTED")
+                .map(|link| if link.node_1_uid == node.uid { &link.node_2_uid } else { &link.node_1_uid })
+                .find_map(|uid| mesh_devices.get(uid.as_str()));
+
+            if let Some(mesh_device) = mesh_device {
+                clients.insert(
+                    node.device_mac_address.to_lowercase(),
+                    (mesh_device.device_name.to_string(), mesh_device.device_mac_address.to_lowercase()),
+                );
+            }
+        }
+
+        Ok(clients)
+    }
+
+    fn get_node_location(&self, node_name: &str, node_mac: &str) -> String {
+        self.mesh_nodes.get(&node_name.to_lowercase())
+            .or(self.mesh_nodes.get(node_mac))
+            .cloned()
+            .unwrap_or_else(|| self.location.to_string())
+    }
+
+    /// Hosts connected to the fritzbox itself are located by their interface type if it is mapped
+    fn get_interface_location(&self, interface_type: &str) -> String {
+        self.interface_locations.get(interface_type)
+            .cloned()
+            .unwrap_or_else(|| self.location.to_string())
+    }
+
+    async fn get(&self, path: &str) -> Result<String, SourceError> {
+        let url = if path.starts_with("http") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.base_url, path)
+        };
+
+        Ok(self.client
+            .get(url)
+            .send()
+            .await?
+            .text()
+            .await?)
+    }
+
+    /// Calls an action of the hosts service, answering the digest authentication challenge if required
+    async fn soap_call(&self, action: &str) -> Result<String, SourceError> {
+        let envelope = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+            <s:Body><u:{} xmlns:u=\"{}\"/></s:Body></s:Envelope>",
+            action, HOSTS_SERVICE,
+        );
+
+        let mut authorization: Option<String> = None;
+        loop {
+            let mut request = self.client
+                .post(format!("{}{}", self.base_url, self.control_url))
+                .header("Content-Type", "text/xml; charset=\"utf-8\"")
+                .header("SOAPAction", format!("{}#{}", HOSTS_SERVICE, action))
+                .body(envelope.to_string());
+
+            if let Some(authorization) = authorization.as_ref() {
+                request = request.header("Authorization", authorization);
+            }
+
+            let response = request.send().await?;
+            if response.status() == StatusCode::UNAUTHORIZED {
+                if authorization.is_some() {
+                    return Err(SourceError::Auth("invalid credentials".to_string()));
+                }
+
+                let challenge = response.headers()
+                    .get(WWW_AUTHENTICATE)
+                    .and_then(|challenge| challenge.to_str().ok())
+                    .ok_or(SourceError::Parse("missing authentication challenge".to_string()))?;
+
+                authorization = Some(self.digest_authorization(challenge)?);
+                continue;
+            }
+
+            let status = response.status();
+            let body = response.text().await?;
+            return if status.is_success() {
+                Ok(body)
+            } else {
+                Err(SourceError::Network(format!("{} failed with status {}", action, status)))
+            };
+        }
+    }
+
+    fn digest_authorization(&self, challenge: &str) -> Result<String, SourceError> {
+        let parameter_regex = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+        let parameters: HashMap<String, String> = parameter_regex.captures_iter(challenge)
+            .map(|parameter| (parameter[1].to_lowercase(), parameter[2].to_string()))
+            .collect();
+
+        let realm = parameters.get("realm")
+            .ok_or(SourceError::Parse("missing realm in authentication challenge".to_string()))?;
+        let nonce = parameters.get("nonce")
+            .ok_or(SourceError::Parse("missing nonce in authentication challenge".to_string()))?;
+        let cnonce = format!("{:x}", Md5::digest(format!("{}{}", nonce, self.ip)));
+
+        let ha1 = md5_hex(&format!("{}:{}:{}", self.user, realm, self.password));
+        let ha2 = md5_hex(&format!("POST:{}", self.control_url));
+        let response = md5_hex(&format!("{}:{}:00000001:{}:auth:{}", ha1, nonce, cnonce, ha2));
+
+        Ok(format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", qop=auth, nc=00000001, cnonce=\"{}\", response=\"{}\"",
+            self.user, realm, nonce, self.control_url, cnonce, response,
+        ))
+    }
+}
+
+fn md5_hex(value: &str) -> String {
+    format!("{:x}", Md5::digest(value))
+}
+
+fn extract_tag(xml: &str, tag: &str) -> Option<String> {
+    let start = format!("<{}>", tag);
+    let end = format!("</{}>", tag);
+
+    let value_start = xml.find(&start)? + start.len();
+    let value_end = value_start + xml[value_start..].find(&end)?;
+
+    Some(xml[value_start..value_end].trim().to_string())
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::net::UnixDatagram;
+use tokio::time;
+
+use crate::{DeviceLocation, Pollable, UpdateSender};
+use crate::device::Hostapd;
+use crate::error::SourceError;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(1000);
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+const BUFFER_SIZE: usize = 8192;
+
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A bound client socket of the hostapd control interface, the socket file is removed on drop
+struct ControlSocket {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+#[async_trait]
+impl Pollable for Hostapd {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        self.get_stations().await
+    }
+}
+
+impl Hostapd {
+    pub fn new(socket: &str, location: &str, updates: UpdateSender) -> Hostapd {
+        let socket = PathBuf::from(socket);
+        tokio::spawn(listen_for_events(socket.clone(), location.to_string(), updates));
+
+        Hostapd {
+            socket,
+            location: location.to_string(),
+        }
+    }
+
+    async fn get_stations(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let control = ControlSocket::connect(&self.socket).await?;
+        let bssid = control.get_bssid().await?;
+
+        let mut stations = Vec::new();
+        let mut response = control.request("STA-FIRST").await?;
+
+        // every response starts with the station address, an empty response ends the list
+        while let Some(mac) = response.lines().next().filter(|mac| !mac.is_empty() && *mac != "FAIL") {
+            let mac = mac.to_lowercase();
+
+            stations.push(station(&mac, &bssid, &self.location));
+
+            response = control.request(&format!("STA-NEXT {}", mac)).await?;
+        }
+
+        Ok(stations)
+    }
+}
+
+fn station(mac: &str, bssid: &str, location: &str) -> DeviceLocation {
+    DeviceLocation {
+        device_mac: mac.to_string(),
+        remote_mac: bssid.to_string(),
+        location: location.to_string(),
+        ..Default::default()
+    }
+}
+
+impl ControlSocket {
+    async fn connect(control_path: &PathBuf) -> Result<ControlSocket, SourceError> {
+        let path = std::env::temp_dir().join(format!(
+            "location2mqtt-hostapd-{}-{}",
+            std::process::id(),
+            SOCKET_COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+
+        let socket = UnixDatagram::bind(&path)?;
+        let control = ControlSocket {
+            socket,
+            path,
+        };
+
+        control.socket.connect(control_path)?;
+        Ok(control)
+    }
+
+    async fn request(&self, command: &str) -> Result<String, SourceError> {
+        self.socket.send(command.as_bytes()).await?;
+
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        loop {
+            let length = time::timeout(REQUEST_TIMEOUT, self.socket.recv(&mut buffer))
+                .await
+                .map_err(|_| SourceError::Timeout)??;
+
+            let response = String::from_utf8_lossy(&buffer[..length]).to_string();
+            // unsolicited event messages start with a priority, e.g. `<3>AP-STA-CONNECTED ...`
+            if !response.starts_with('<') {
+                return Ok(response);
+            }
+        }
+    }
+
+    async fn get_bssid(&self) -> Result<String, SourceError> {
+        let config = self.request("GET_CONFIG").await?;
+
+        Ok(config.lines()
+            .find_map(|line| line.strip_prefix("bssid="))
+            .unwrap_or_default()
+            .to_lowercase())
+    }
+
+    async fn receive(&self, buffer: &mut [u8]) -> Result<String, SourceError> {
+        let length = self.socket.recv(buffer).await?;
+        Ok(String::from_utf8_lossy(&buffer[..length]).to_string())
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Attaches to the control interface and pushes every station that connects or disconnects
+async fn listen_for_events(control_path: PathBuf, location: String, updates: UpdateSender) {
+    loop {
+        if let Err(error) = receive_events(&control_path, &location, &updates).await {
+            println!("hostapd {}: event listener failed, reconnecting: {}", control_path.display(), error);
+        }
+
+        time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn receive_events(control_path: &PathBuf, location: &str, updates: &UpdateSender) -> Result<(), SourceError> {
+    let control = ControlSocket::connect(control_path).await?;
+    let bssid = control.get_bssid().await?;
+    if control.request("ATTACH").await?.trim() != "OK" {
+        return Err(SourceError::Io("failed to attach to control interface".to_string()));
+    }
+
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        // hostapd drops attached monitors that stop responding, so ping while idle
+        let event = match time::timeout(Duration::from_secs(30), control.receive(&mut buffer)).await {
+            Ok(event) => event?,
+            Err(_) => {
+                control.socket.send(b"PING").await?;
+                continue;
+            }
+        };
+
+        // events look like `<3>AP-STA-CONNECTED aa:bb:cc:dd:ee:ff`
+        let mut words = event.split_whitespace();
+        let (Some(event), Some(mac)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let mac = mac.to_lowercase();
+
+        if event.ends_with("AP-STA-CONNECTED") {
+            updates.upsert(station(&mac, &bssid, location));
+        } else if event.ends_with("AP-STA-DISCONNECTED") {
+            updates.remove(&mac);
+        }
+    }
+}
@@ -1,9 +1,11 @@
+use std::net::IpAddr;
+
 use async_trait::async_trait;
-use reqwest::Error;
 use serde::Deserialize;
 
 use crate::{DeviceLocation, Pollable};
-use crate::device::HPSwitch;
+use crate::device::{normalize_mac, HPSwitch};
+use crate::error::SourceError;
 
 #[derive(Deserialize)]
 struct MacTable {
@@ -19,28 +21,29 @@ struct MacTableEntry {
 
 #[async_trait]
 impl Pollable for HPSwitch {
-    async fn poll_device(&self) -> Vec<DeviceLocation> {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
         let mut clients: Vec<DeviceLocation> = Vec::new();
-        let result = self.get_mac_table().await.ok();
-
-        if result.is_some() {
-            let result = result.unwrap();
+        let result = self.get_mac_table().await?;
+        for item in result.mac_table_entry_element.iter() {
+            if item.vlan_id == 23 && self.ports.contains(&item.port_id) {
+                let Some(device_mac) = normalize_mac(&item.mac_address) else {
+                    continue;
+                };
 
-            for item in result.mac_table_entry_element.iter() {
-                if item.vlan_id == 23 && self.ports.contains(&item.port_id) {
-                    clients.push(DeviceLocation {
-                        ipv4: "".to_string(),
-                        ipv6: Vec::new(),
-                        device_mac: self.format_hp_mac(item.mac_address.to_string()),
-                        remote_mac: "".to_string(),
-                        remote_ip: self.ip.to_string(),
-                        location: self.location.to_string(),
-                    });
-                }
+                clients.push(DeviceLocation {
+                    device_mac,
+                    remote_ip: self.ip.to_string(),
+                    location: self.location.to_string(),
+                    ..Default::default()
+                });
             }
         }
 
-        return clients;
+        Ok(clients)
+    }
+
+    fn address(&self) -> Option<IpAddr> {
+        self.ip.parse().ok()
     }
 }
 
@@ -60,7 +63,7 @@ impl HPSwitch {
         }
     }
 
-    async fn get_mac_table(&self) -> Result<MacTable, Option<Error>> {
+    async fn get_mac_table(&self) -> Result<MacTable, SourceError> {
         let body = self.client
             .get(&self.data_url)
             .send()
@@ -68,22 +71,6 @@ impl HPSwitch {
             .text()
             .await?;
 
-        let mac_table: Option<MacTable> = serde_json::from_str(&body).ok();
-
-        return if mac_table.is_some() {
-            Ok(mac_table.unwrap())
-        } else {
-            Err(None)
-        };
-    }
-
-    fn format_hp_mac(&self, mut mac: String) -> String {
-        mac = mac.replace('-', "");
-        mac.insert(2, ':');
-        mac.insert(5, ':');
-        mac.insert(8, ':');
-        mac.insert(11, ':');
-        mac.insert(14, ':');
-        mac.to_string()
+        Ok(serde_json::from_str(&body)?)
     }
 }
\ No newline at end of file
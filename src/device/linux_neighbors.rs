@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use netlink_packet_route::{AF_INET, AF_INET6, NUD_FAILED, NUD_INCOMPLETE, NUD_NOARP};
+use netlink_packet_route::nlas::link::Nla as LinkNla;
+use netlink_packet_route::nlas::neighbour::Nla as NeighbourNla;
+use tokio::fs;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::LinuxNeighbors;
+use crate::error::SourceError;
+
+const PROC_NET_ARP: &str = "/proc/net/arp";
+const ATF_COM: u32 = 0x2;
+
+struct Neighbor {
+    mac: String,
+    address: String,
+    ipv6: bool,
+}
+
+#[async_trait]
+impl Pollable for LinuxNeighbors {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let neighbors = match self.get_netlink_neighbors().await {
+            Ok(neighbors) => neighbors,
+            Err(error) => {
+                println!("failed to read neighbor table via netlink, falling back to {}: {}", PROC_NET_ARP, error);
+
+                self.get_proc_neighbors().await
+                    .map_err(|error| SourceError::Io(format!("failed to read {}: {}", PROC_NET_ARP, error)))?
+            }
+        };
+
+        let mut clients: HashMap<String, DeviceLocation> = HashMap::new();
+        for neighbor in neighbors {
+            let client = clients.entry(neighbor.mac.to_string()).or_insert_with(|| DeviceLocation {
+                device_mac: neighbor.mac,
+                ..Default::default()
+            });
+
+            if neighbor.ipv6 {
+                client.ipv6.push(neighbor.address);
+            } else if client.ipv4.is_empty() {
+                client.ipv4 = neighbor.address;
+            }
+        }
+
+        Ok(clients.into_values().collect())
+    }
+}
+
+impl LinuxNeighbors {
+    pub fn new(interfaces: Vec<String>, ipv6: bool) -> LinuxNeighbors {
+        LinuxNeighbors {
+            interfaces,
+            ipv6,
+        }
+    }
+
+    async fn get_netlink_neighbors(&self) -> Result<Vec<Neighbor>, String> {
+        let (connection, handle, _) = rtnetlink::new_connection().map_err(|error| error.to_string())?;
+        let connection = tokio::spawn(connection);
+
+        let mut interface_names: HashMap<u32, String> = HashMap::new();
+        let mut links = handle.link().get().execute();
+        while let Some(link) = links.try_next().await.map_err(|error| error.to_string())? {
+            for nla in link.nlas {
+                if let LinkNla::IfName(name) = nla {
+                    interface_names.insert(link.header.index, name);
+                }
+            }
+        }
+
+        let mut neighbors = Vec::new();
+        let mut entries = handle.neighbours().get().execute();
+        while let Some(entry) = entries.try_next().await.map_err(|error| error.to_string())? {
+            let family = entry.header.family as u16;
+            if entry.header.state & (NUD_INCOMPLETE | NUD_FAILED | NUD_NOARP) != 0
+                || !(family == AF_INET || (self.ipv6 && family == AF_INET6)) {
+                continue;
+            }
+
+            let interface = interface_names.get(&entry.header.ifindex).map(String::as_str).unwrap_or_default();
+            if !self.is_interface_included(interface) {
+                continue;
+            }
+
+            let mut mac = None;
+            let mut address = None;
+            for nla in entry.nlas {
+                match nla {
+                    NeighbourNla::LinkLocalAddress(bytes) if bytes.len() == 6 => mac = Some(format_mac(&bytes)),
+                    NeighbourNla::Destination(bytes) => address = format_address(&bytes),
+                    _ => {}
+                }
+            }
+
+            if let (Some(mac), Some(address)) = (mac, address) {
+                neighbors.push(Neighbor {
+                    mac,
+                    address,
+                    ipv6: family == AF_INET6,
+                });
+            }
+        }
+
+        connection.abort();
+        Ok(neighbors)
+    }
+
+    /// Reads the IPv4 neighbor table from procfs, IPv6 neighbors are only exposed via netlink
+    async fn get_proc_neighbors(&self) -> Result<Vec<Neighbor>, std::io::Error> {
+        let table = fs::read_to_string(PROC_NET_ARP).await?;
+
+        Ok(table.lines()
+            .skip(1)
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if columns.len() < 6 {
+                    return None;
+                }
+
+                let flags = u32::from_str_radix(columns[2].trim_start_matches("0x"), 16).unwrap_or_default();
+                if flags & ATF_COM == 0 || !self.is_interface_included(columns[5]) {
+                    return None;
+                }
+
+                Some(Neighbor {
+                    mac: columns[3].to_lowercase(),
+                    address: columns[0].to_string(),
+                    ipv6: false,
+                })
+            })
+            .collect())
+    }
+
+    fn is_interface_included(&self, interface: &str) -> bool {
+        self.interfaces.is_empty() || self.interfaces.iter().any(|included| included == interface)
+    }
+}
+
+fn format_mac(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn format_address(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+        16 => {
+            let bytes: [u8; 16] = bytes.try_into().ok()?;
+            Some(Ipv6Addr::from(bytes).to_string())
+        }
+        _ => None,
+    }
+}
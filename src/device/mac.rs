@@ -0,0 +1,13 @@
+/// Accepts `aa:bb:cc:dd:ee:ff`, `AA-BB-CC-DD-EE-FF`, `aabb.ccdd.eeff` and `aabbccddeeff`
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let hex: String = mac.chars()
+        .filter(|char| !matches!(char, ':' | '-' | '.'))
+        .collect::<String>()
+        .to_lowercase();
+
+    if hex.len() != 12 || !hex.chars().all(|char| char.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some((0..6).map(|index| &hex[index * 2..index * 2 + 2]).collect::<Vec<&str>>().join(":"))
+}
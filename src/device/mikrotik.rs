@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::MikroTik;
+use crate::error::SourceError;
+
+#[derive(Deserialize)]
+struct BridgeHost {
+    #[serde(rename = "mac-address")]
+    mac_address: String,
+    #[serde(rename = "on-interface")]
+    on_interface: Option<String>,
+    bridge: Option<String>,
+    local: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArpEntry {
+    #[serde(rename = "mac-address")]
+    mac_address: Option<String>,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct DhcpLease {
+    #[serde(rename = "mac-address")]
+    mac_address: Option<String>,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct Registration {
+    #[serde(rename = "mac-address")]
+    mac_address: String,
+    interface: String,
+}
+
+#[async_trait]
+impl Pollable for MikroTik {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let mut clients: HashMap<String, DeviceLocation> = HashMap::new();
+
+        let bridge_hosts: Vec<BridgeHost> = self.get("interface/bridge/host").await?.unwrap_or_default();
+        let bridge_macs: HashMap<String, String> = bridge_hosts.iter()
+            .filter(|host| host.local.as_deref() == Some("true") && host.on_interface == host.bridge)
+            .filter_map(|host| Some((host.bridge.clone()?, host.mac_address.to_lowercase())))
+            .collect();
+
+        for host in bridge_hosts.iter().filter(|host| host.local.as_deref() != Some("true")) {
+            let interface = host.on_interface.as_deref().unwrap_or_default();
+            let remote_mac = host.bridge.as_ref()
+                .and_then(|bridge| bridge_macs.get(bridge))
+                .cloned()
+                .unwrap_or_default();
+
+            self.add_client(&mut clients, &host.mac_address, interface, remote_mac);
+        }
+
+        // the bridge a wireless interface is a port of, learned from the hosts seen on it
+        let interface_bridges: HashMap<&str, &str> = bridge_hosts.iter()
+            .filter_map(|host| Some((host.on_interface.as_deref()?, host.bridge.as_deref()?)))
+            .collect();
+
+        // wireless clients are also learned on the bridge, the registration table is more accurate though
+        for path in ["interface/wifi/registration-table", "interface/wireless/registration-table"] {
+            let registrations: Vec<Registration> = self.get(path).await?.unwrap_or_default();
+
+            for registration in registrations.iter() {
+                let remote_mac = interface_bridges.get(registration.interface.as_str())
+                    .and_then(|bridge| bridge_macs.get(*bridge))
+                    .cloned()
+                    .unwrap_or_default();
+
+                self.add_client(&mut clients, &registration.mac_address, &registration.interface, remote_mac);
+            }
+        }
+
+        let arp_entries: Vec<ArpEntry> = self.get("ip/arp").await?.unwrap_or_default();
+        let leases: Vec<DhcpLease> = self.get("ip/dhcp-server/lease").await?.unwrap_or_default();
+
+        let addresses = arp_entries.iter()
+            .map(|entry| (&entry.mac_address, &entry.address))
+            .chain(leases.iter().map(|lease| (&lease.mac_address, &lease.address)));
+
+        for (mac, address) in addresses {
+            let Some(mac) = mac.as_ref() else {
+                continue;
+            };
+
+            let client = clients.entry(mac.to_lowercase()).or_insert_with(|| DeviceLocation {
+                device_mac: mac.to_lowercase(),
+                ..Default::default()
+            });
+
+            if client.ipv4.is_empty() {
+                client.ipv4 = address.to_string();
+            }
+        }
+
+        Ok(clients.into_values().collect())
+    }
+
+    fn address(&self) -> Option<IpAddr> {
+        self.ip.parse().ok()
+    }
+}
+
+impl MikroTik {
+    pub fn new(ip: &str, user: &str, password: &str, ports: HashMap<String, String>) -> MikroTik {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+
+        MikroTik {
+            ip: ip.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+            base_url: format!("https://{}/rest", ip),
+            ports,
+            client,
+        }
+    }
+
+    /// Returns `None` for menus of packages that are not installed, e.g. wifi vs. wireless
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, SourceError> {
+        let response = self.client
+            .get(format!("{}/{}", self.base_url, path))
+            .basic_auth(&self.user, Some(&self.password))
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND => return Ok(None),
+            StatusCode::UNAUTHORIZED => return Err(SourceError::Auth("invalid credentials".to_string())),
+            status => return Err(SourceError::Network(format!("/{} returned {}", path, status))),
+        }
+
+        let body = response.text().await?;
+        serde_json::from_str(&body)
+            .map(Some)
+            .map_err(|error| SourceError::Parse(format!("/{}: {}", path, error)))
+    }
+
+    fn add_client(&self, clients: &mut HashMap<String, DeviceLocation>, mac: &str, interface: &str, remote_mac: String) {
+        if let Some(location) = self.ports.get(interface) {
+            clients.insert(mac.to_lowercase(), DeviceLocation {
+                device_mac: mac.to_lowercase(),
+                remote_ip: self.ip.to_string(),
+                remote_mac,
+                location: location.to_string(),
+                ..Default::default()
+            });
+        }
+    }
+}
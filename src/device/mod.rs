@@ -1,9 +1,39 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use notify::RecommendedWatcher;
+use regex::Regex;
 use reqwest::Client;
+use serde::Deserialize;
+use wasmi::{Engine, Linker, Module};
+
+use crate::DeviceLocation;
 
 mod hp_switch;
 mod unifi_controller;
 mod opnsense;
+mod omada_controller;
+mod mikrotik;
+mod openwrt;
+mod fritzbox;
+mod linux_neighbors;
+mod dhcp_leases;
+mod hostapd;
+mod radius;
+mod syslog;
+mod snmp_traps;
+mod mqtt_presence;
+mod external_command;
+mod wasm_plugin;
+mod restconf_switch;
+mod ssh_switch;
+mod textfsm;
+mod mac;
+
+pub use mac::normalize_mac;
+pub use textfsm::TextFsm;
 
 pub struct HPSwitch {
     ip: String,
@@ -13,25 +43,241 @@ pub struct HPSwitch {
     client: Client,
 }
 
+#[derive(Clone)]
 pub struct UniFiController {
     ip: String,
     data_url: String,
+    command_url: String,
     login_url: String,
     client: Client,
     user: String,
     password: String,
+    uses_api_key: bool,
     access_points: HashMap<String, UniFiAP>,
 }
 
+#[derive(Clone)]
 pub struct UniFiAP {
-    pub mac: String,
     pub location: String,
 }
 
 pub struct OPNSense {
-    ip: String,
     api_key: String,
     api_secret: String,
     client: Client,
     data_url: String,
-}
\ No newline at end of file
+}
+
+pub struct OmadaController {
+    omadac_id: String,
+    client_id: String,
+    client_secret: String,
+    token_url: String,
+    data_url: String,
+    access_token: Mutex<Option<String>>,
+    client: Client,
+    devices: HashMap<String, OmadaDevice>,
+}
+
+pub struct OmadaDevice {
+    pub location: String,
+}
+
+pub struct MikroTik {
+    ip: String,
+    user: String,
+    password: String,
+    base_url: String,
+    ports: HashMap<String, String>,
+    client: Client,
+}
+
+pub struct OpenWrt {
+    ip: String,
+    user: String,
+    password: String,
+    ubus_url: String,
+    session: Mutex<Option<String>>,
+    locations: Vec<OpenWrtLocation>,
+    client: Client,
+}
+
+pub struct OpenWrtLocation {
+    pub device: Option<String>,
+    pub ssid: Option<String>,
+    pub location: String,
+}
+
+pub struct FritzBox {
+    ip: String,
+    user: String,
+    password: String,
+    location: String,
+    base_url: String,
+    control_url: String,
+    mesh_nodes: HashMap<String, String>,
+    interface_locations: HashMap<String, String>,
+    client: Client,
+}
+
+pub struct LinuxNeighbors {
+    interfaces: Vec<String>,
+    ipv6: bool,
+}
+
+pub struct DhcpLeaseFile {
+    path: PathBuf,
+    format: DhcpLeaseFormat,
+    _watcher: Option<RecommendedWatcher>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DhcpLeaseFormat {
+    Dnsmasq,
+    Isc,
+    Kea,
+}
+
+pub struct Hostapd {
+    socket: PathBuf,
+    location: String,
+}
+
+pub struct RadiusAccounting {
+    sessions: Arc<Mutex<HashMap<String, RadiusSession>>>,
+    session_timeout: Duration,
+    bind_error: Arc<Mutex<Option<String>>>,
+}
+
+/// The current accounting session of a client, identified by the NAS and its Acct-Session-Id
+pub struct RadiusSession {
+    id: String,
+    tracked: TrackedClient,
+}
+
+/// A client reported by a listening source, kept until it is removed or times out
+pub struct TrackedClient {
+    client: DeviceLocation,
+    updated: Instant,
+}
+
+pub struct Syslog {
+    clients: Arc<Mutex<HashMap<String, SyslogClient>>>,
+    client_timeout: Duration,
+    bind_error: Arc<Mutex<Option<String>>>,
+}
+
+/// A client seen in syslog together with the host and port of the last message about it
+pub struct SyslogClient {
+    sender: IpAddr,
+    port: Option<String>,
+    tracked: TrackedClient,
+}
+
+/// Named groups `mac`, `ip`, `hostname`, `port` and `event` are extracted from matching messages
+pub struct SyslogRule {
+    pub regex: Regex,
+    pub remove: bool,
+    pub remove_events: Vec<String>,
+    pub location: Option<String>,
+}
+
+pub struct SnmpTrapReceiver {
+    clients: Arc<Mutex<HashMap<String, SnmpTrapClient>>>,
+    client_timeout: Duration,
+    bind_error: Arc<Mutex<Option<String>>>,
+}
+
+/// A client learned from a mac notification together with the switch and port that reported it
+pub struct SnmpTrapClient {
+    agent: IpAddr,
+    port: String,
+    tracked: TrackedClient,
+}
+
+pub struct SnmpTrapSwitch {
+    pub ports: Vec<String>,
+    pub location: String,
+}
+
+pub struct MqttPresence {
+    clients: Arc<Mutex<HashMap<String, PresenceReading>>>,
+    client_timeout: Duration,
+}
+
+pub struct PresenceReading {
+    tracked: TrackedClient,
+    distance: Option<f64>,
+}
+
+pub struct MqttPresenceMapping {
+    pub mac: MqttPresenceField,
+    pub location: MqttPresenceField,
+    pub distance: Option<MqttPresenceField>,
+    pub ip: Option<MqttPresenceField>,
+    pub hostname: Option<MqttPresenceField>,
+    pub user: Option<MqttPresenceField>,
+    pub devices: HashMap<String, String>,
+}
+
+/// Where a value of a presence message is read from, either a topic level or a (nested) payload field
+pub enum MqttPresenceField {
+    Topic(usize),
+    Payload(Vec<String>),
+}
+
+pub struct ExternalCommand {
+    command: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+pub struct WasmPlugin {
+    name: String,
+    engine: Engine,
+    module: Arc<Module>,
+    linker: Arc<Linker<wasm_plugin::PluginState>>,
+    config: String,
+    allowed_hosts: Vec<String>,
+    client: Client,
+    memory_limit: usize,
+    fuel: u64,
+    timeout: Duration,
+}
+
+pub struct RestconfSwitch {
+    ip: String,
+    ports: Vec<String>,
+    location: String,
+    vlan: Option<u16>,
+    mapping: RestconfMapping,
+    data_url: String,
+    user: String,
+    password: String,
+    client: Client,
+}
+
+/// Where the mac table of a yang model is found, every path is a list of container names
+pub struct RestconfMapping {
+    pub path: String,
+    pub entries: Vec<String>,
+    pub mac: Vec<String>,
+    pub vlan: Vec<String>,
+    pub interface: Vec<String>,
+}
+
+pub struct SshSwitch {
+    ip: String,
+    address: String,
+    user: String,
+    password: String,
+    /// Unpadded base64 of the SHA256 hash of the host key
+    host_key: String,
+    command: String,
+    template: TextFsm,
+    ports: Vec<String>,
+    location: String,
+    vlan: Option<u16>,
+    timeout: Duration,
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use rumqttc::Publish;
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{DeviceLocation, Pollable, UpdateSender};
+use crate::device::{normalize_mac, MqttPresence, MqttPresenceField, MqttPresenceMapping, PresenceReading, TrackedClient};
+use crate::error::SourceError;
+
+#[async_trait]
+impl Pollable for MqttPresence {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let mut clients = self.clients.lock().unwrap();
+
+        clients.retain(|_, reading| reading.tracked.updated.elapsed() < self.client_timeout);
+        Ok(clients.values().map(|reading| reading.tracked.client.clone()).collect())
+    }
+}
+
+impl MqttPresence {
+    pub fn new(messages: UnboundedReceiver<Publish>, mapping: MqttPresenceMapping, client_timeout: Duration, updates: UpdateSender) -> MqttPresence {
+        let clients: Arc<Mutex<HashMap<String, PresenceReading>>> = Default::default();
+
+        tokio::spawn(receive_messages(messages, mapping, clients.clone(), client_timeout, updates));
+
+        MqttPresence {
+            clients,
+            client_timeout,
+        }
+    }
+}
+
+async fn receive_messages(
+    mut messages: UnboundedReceiver<Publish>,
+    mapping: MqttPresenceMapping,
+    clients: Arc<Mutex<HashMap<String, PresenceReading>>>,
+    client_timeout: Duration,
+    updates: UpdateSender,
+) {
+    while let Some(message) = messages.recv().await {
+        let topic: Vec<&str> = message.topic.split('/').collect();
+        let payload: Value = serde_json::from_slice(&message.payload)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&message.payload).to_string()));
+
+        let get = |field: Option<&MqttPresenceField>| field
+            .and_then(|field| field.extract(&topic, &payload))
+            .unwrap_or_default();
+
+        let id = get(Some(&mapping.mac));
+        let location = get(Some(&mapping.location));
+        if id.is_empty() || location.is_empty() {
+            continue;
+        }
+
+        // devices without a stable mac address, e.g. BLE tags with random addresses, can be mapped to one
+        let mac = mapping.devices.get(&id).cloned()
+            .or_else(|| normalize_mac(&id))
+            .unwrap_or(id);
+        let distance = get(mapping.distance.as_ref()).parse::<f64>().ok();
+
+        let mut clients = clients.lock().unwrap();
+
+        // several rooms report the same device, the closest recent reading wins
+        if let Some(current) = clients.get(&mac)
+            && current.tracked.client.location != location
+            && current.tracked.updated.elapsed() < client_timeout
+            && let (Some(current_distance), Some(distance)) = (current.distance, distance)
+            && current_distance < distance {
+            continue;
+        }
+
+        let client = DeviceLocation {
+            ipv4: get(mapping.ip.as_ref()),
+            device_mac: mac.to_string(),
+            hostname: get(mapping.hostname.as_ref()),
+            user: get(mapping.user.as_ref()),
+            location,
+            ..Default::default()
+        };
+
+        // readings are repeated every few seconds, only a changed room is worth an update
+        let changed = clients.get(&mac).is_none_or(|current| current.tracked.client != client);
+
+        clients.insert(mac, PresenceReading {
+            tracked: TrackedClient {
+                client: client.clone(),
+                updated: Instant::now(),
+            },
+            distance,
+        });
+        if changed {
+            updates.upsert(client);
+        }
+    }
+}
+
+impl MqttPresenceField {
+    fn extract(&self, topic: &[&str], payload: &Value) -> Option<String> {
+        match self {
+            MqttPresenceField::Topic(level) => topic.get(*level).map(|level| level.to_string()),
+            MqttPresenceField::Payload(path) => {
+                let value = path.iter().try_fold(payload, |value, key| value.get(key))?;
+
+                match value {
+                    Value::String(value) => Some(value.to_string()),
+                    Value::Null => None,
+                    value => Some(value.to_string()),
+                }
+            }
+        }
+    }
+}
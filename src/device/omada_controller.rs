@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::{normalize_mac, OmadaController, OmadaDevice};
+use crate::error::SourceError;
+
+const OMADA_PAGE_SIZE: usize = 1000;
+const OMADA_TOKEN_EXPIRED: i32 = -44112;
+const OMADA_TOKEN_INVALID: i32 = -44113;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OmadaResponse<T> {
+    error_code: i32,
+    msg: Option<String>,
+    result: Option<T>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OmadaTokenRequest {
+    omadac_id: String,
+    client_id: String,
+    client_secret: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OmadaToken {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OmadaClientPage {
+    total_rows: usize,
+    data: Vec<OmadaClient>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OmadaClient {
+    mac: String,
+    ip: Option<String>,
+    ipv6_list: Option<Vec<String>>,
+    #[serde(default)]
+    wireless: bool,
+    ap_mac: Option<String>,
+    switch_mac: Option<String>,
+    /// The switch port of wired clients
+    port: Option<u32>,
+}
+
+#[async_trait]
+impl Pollable for OmadaController {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let mut clients: Vec<DeviceLocation> = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let url = format!("{}?page={}&pageSize={}", self.data_url, page, OMADA_PAGE_SIZE);
+            let result: OmadaClientPage = self.authenticated_request(&url).await?;
+
+            let received = result.data.len();
+            clients.extend(result.data.iter().filter_map(|client| self.to_device_location(client)));
+
+            if received == 0 || clients.len() >= result.total_rows {
+                return Ok(clients);
+            }
+            page += 1;
+        }
+    }
+}
+
+impl OmadaController {
+    pub fn new(url: &str, omadac_id: &str, site_id: &str, client_id: &str, client_secret: &str, devices: HashMap<String, OmadaDevice>) -> OmadaController {
+        let client = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+
+        let url = url.trim_end_matches('/');
+
+        OmadaController {
+            omadac_id: omadac_id.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            token_url: format!("{}/openapi/authorize/token?grant_type=client_credentials", url),
+            data_url: format!("{}/openapi/v1/{}/sites/{}/clients", url, omadac_id, site_id),
+            access_token: Default::default(),
+            client,
+            devices,
+        }
+    }
+
+    /// Requests a new access token using the client credentials of the OpenAPI application
+    async fn authorize(&self) -> Result<String, SourceError> {
+        let request = OmadaTokenRequest {
+            omadac_id: self.omadac_id.to_string(),
+            client_id: self.client_id.to_string(),
+            client_secret: self.client_secret.to_string(),
+        };
+
+        let body = self.client
+            .post(&self.token_url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&request)?)
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        let response: OmadaResponse<OmadaToken> = serde_json::from_str(&body)?;
+
+        match response.result {
+            Some(token) if response.error_code == 0 => Ok(token.access_token),
+            _ => Err(SourceError::Auth(response.msg.unwrap_or_default())),
+        }
+    }
+
+    /// Sends a GET request with the current access token, a new token is requested once if it is missing or expired
+    async fn authenticated_request<T: DeserializeOwned>(&self, url: &str) -> Result<T, SourceError> {
+        let mut refreshed = false;
+
+        loop {
+            let token = self.access_token.lock().unwrap().clone();
+            let token = match token {
+                Some(token) => token,
+                None => {
+                    let token = self.authorize().await?;
+                    refreshed = true;
+                    *self.access_token.lock().unwrap() = Some(token.to_string());
+                    token
+                }
+            };
+
+            let body = self.client
+                .get(url)
+                .header("Authorization", format!("AccessToken={}", token))
+                .send()
+                .await?
+                .text()
+                .await?;
+
+            let response: OmadaResponse<T> = serde_json::from_str(&body)?;
+
+            match response.error_code {
+                0 => return response.result.ok_or(SourceError::Parse("empty response".to_string())),
+                OMADA_TOKEN_EXPIRED | OMADA_TOKEN_INVALID if !refreshed => {
+                    *self.access_token.lock().unwrap() = None;
+                }
+                OMADA_TOKEN_EXPIRED | OMADA_TOKEN_INVALID => {
+                    return Err(SourceError::Auth("access token rejected after refresh".to_string()));
+                }
+                // the controller answered, but refused the request, asking again will not change that
+                error_code => {
+                    return Err(SourceError::Parse(format!("api error {}: {}", error_code, response.msg.unwrap_or_default())));
+                }
+            }
+        }
+    }
+
+    /// Omada reports mac addresses as `AA-BB-CC-DD-EE-FF`, clients without a valid one are skipped
+    fn to_device_location(&self, client: &OmadaClient) -> Option<DeviceLocation> {
+        let (remote_mac, port) = if client.wireless {
+            (client.ap_mac.as_ref(), None)
+        } else {
+            (client.switch_mac.as_ref(), client.port)
+        };
+        let remote_mac = remote_mac
+            .and_then(|mac| normalize_mac(mac))
+            .unwrap_or_default();
+
+        let location = self.devices
+            .get(&remote_mac)
+            .map(|device| device.location.to_string())
+            .unwrap_or_default();
+
+        Some(DeviceLocation {
+            ipv4: client.ip.clone().unwrap_or_default(),
+            ipv6: client.ipv6_list.clone().unwrap_or_default(),
+            device_mac: normalize_mac(&client.mac)?,
+            remote_mac,
+            port: port.map(|port| port.to_string()).unwrap_or_default(),
+            location,
+            ..Default::default()
+        })
+    }
+}
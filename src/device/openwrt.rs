@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::{OpenWrt, OpenWrtLocation};
+use crate::error::SourceError;
+
+const UBUS_NULL_SESSION: &str = "00000000000000000000000000000000";
+const UBUS_STATUS_PERMISSION_DENIED: i64 = 6;
+const UBUS_ACCESS_DENIED: i64 = -32002;
+
+#[derive(Deserialize)]
+struct UbusResponse {
+    result: Option<Vec<Value>>,
+    error: Option<UbusError>,
+}
+
+#[derive(Deserialize)]
+struct UbusError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct UbusSession {
+    ubus_rpc_session: String,
+}
+
+#[derive(Deserialize)]
+struct IwinfoDevices {
+    devices: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct IwinfoInfo {
+    ssid: Option<String>,
+    bssid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IwinfoAssocList {
+    results: Vec<IwinfoStation>,
+}
+
+#[derive(Deserialize)]
+struct IwinfoStation {
+    mac: String,
+}
+
+#[derive(Deserialize)]
+struct DhcpLeases {
+    #[serde(default)]
+    dhcp_leases: Vec<DhcpLease>,
+    #[serde(default)]
+    dhcp6_leases: Vec<Dhcp6Lease>,
+}
+
+#[derive(Deserialize)]
+struct DhcpLease {
+    macaddr: Option<String>,
+    ipaddr: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Dhcp6Lease {
+    macaddr: Option<String>,
+    #[serde(default)]
+    ip6addrs: Vec<String>,
+}
+
+enum UbusCallError {
+    SessionExpired,
+    Failed(SourceError),
+}
+
+#[async_trait]
+impl Pollable for OpenWrt {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let mut clients: HashMap<String, DeviceLocation> = HashMap::new();
+
+        let devices: IwinfoDevices = self.call("iwinfo", "devices", json!({})).await?;
+
+        for device in devices.devices.iter() {
+            let info: Option<IwinfoInfo> = self.call("iwinfo", "info", json!({ "device": device })).await.ok();
+            let ssid = info.as_ref().and_then(|info| info.ssid.clone()).unwrap_or_default();
+            let Some(location) = self.get_location(device, &ssid) else {
+                continue;
+            };
+
+            let stations: IwinfoAssocList = match self.call("iwinfo", "assoclist", json!({ "device": device })).await {
+                Ok(stations) => stations,
+                Err(error) => {
+                    println!("openwrt {}: assoclist for {}: {}", self.ip, device, error);
+                    continue;
+                }
+            };
+
+            let bssid = info.and_then(|info| info.bssid).unwrap_or_default().to_lowercase();
+            for station in stations.results.iter() {
+                let mac = station.mac.to_lowercase();
+                clients.insert(mac.to_string(), DeviceLocation {
+                    device_mac: mac,
+                    remote_ip: self.ip.to_string(),
+                    remote_mac: bssid.to_string(),
+                    location: location.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        match self.call::<DhcpLeases>("luci-rpc", "getDHCPLeases", json!({})).await {
+            Ok(leases) => {
+                for lease in leases.dhcp_leases.iter() {
+                    if let (Some(mac), Some(ip)) = (&lease.macaddr, &lease.ipaddr) {
+                        let client = get_or_insert_client(&mut clients, mac);
+                        if client.ipv4.is_empty() {
+                            client.ipv4 = ip.to_string();
+                        }
+                    }
+                }
+
+                for lease in leases.dhcp6_leases.iter() {
+                    if let Some(mac) = &lease.macaddr {
+                        let client = get_or_insert_client(&mut clients, mac);
+                        client.ipv6.extend(lease.ip6addrs.iter().cloned());
+                    }
+                }
+            }
+            Err(error) => println!("openwrt {}: failed to read dhcp leases: {}", self.ip, error),
+        }
+
+        Ok(clients.into_values().collect())
+    }
+}
+
+impl OpenWrt {
+    pub fn new(ip: &str, user: &str, password: &str, locations: Vec<OpenWrtLocation>) -> OpenWrt {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+
+        OpenWrt {
+            ip: ip.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+            ubus_url: format!("http://{}/ubus", ip),
+            session: Default::default(),
+            locations,
+            client,
+        }
+    }
+
+    /// Calls a ubus method, logging in first if there is no session or the session has expired
+    async fn call<T: DeserializeOwned>(&self, object: &str, method: &str, args: Value) -> Result<T, SourceError> {
+        let mut logged_in = false;
+
+        loop {
+            let session = self.session.lock().unwrap().clone();
+            let session = match session {
+                Some(session) => session,
+                None => {
+                    let session = self.login().await?;
+                    logged_in = true;
+                    *self.session.lock().unwrap() = Some(session.to_string());
+                    session
+                }
+            };
+
+            match self.send(&session, object, method, args.clone()).await {
+                Ok(result) => return Ok(serde_json::from_value(result)?),
+                Err(UbusCallError::SessionExpired) if !logged_in => {
+                    *self.session.lock().unwrap() = None;
+                }
+                Err(UbusCallError::SessionExpired) => return Err(SourceError::Auth(format!("access to {}.{} denied", object, method))),
+                Err(UbusCallError::Failed(error)) => return Err(error),
+            }
+        }
+    }
+
+    async fn login(&self) -> Result<String, SourceError> {
+        let args = json!({ "username": self.user, "password": self.password });
+
+        match self.send(UBUS_NULL_SESSION, "session", "login", args).await {
+            Ok(result) => Ok(serde_json::from_value::<UbusSession>(result)?.ubus_rpc_session),
+            Err(UbusCallError::SessionExpired) => Err(SourceError::Auth("invalid credentials".to_string())),
+            Err(UbusCallError::Failed(error)) => Err(error),
+        }
+    }
+
+    async fn send(&self, session: &str, object: &str, method: &str, args: Value) -> Result<Value, UbusCallError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "call",
+            "params": [session, object, method, args],
+        });
+
+        let body = self.client
+            .post(&self.ubus_url)
+            .body(request.to_string())
+            .send()
+            .await
+            .map_err(|error| UbusCallError::Failed(error.into()))?
+            .text()
+            .await
+            .map_err(|error| UbusCallError::Failed(error.into()))?;
+
+        let response: UbusResponse = serde_json::from_str(&body)
+            .map_err(|error| UbusCallError::Failed(error.into()))?;
+
+        if let Some(error) = response.error {
+            return if error.code == UBUS_ACCESS_DENIED {
+                Err(UbusCallError::SessionExpired)
+            } else {
+                Err(UbusCallError::Failed(SourceError::Parse(error.message)))
+            };
+        }
+
+        // successful calls return [0, { ... }], failed calls only contain the status code.
+        // The router answered, so other errors will not go away by retrying.
+        let mut result = response.result.unwrap_or_default().into_iter();
+        match result.next().and_then(|status| status.as_i64()) {
+            Some(0) => Ok(result.next().unwrap_or(json!({}))),
+            Some(UBUS_STATUS_PERMISSION_DENIED) => Err(UbusCallError::SessionExpired),
+            Some(status) => Err(UbusCallError::Failed(SourceError::Parse(format!("{}.{} returned status {}", object, method, status)))),
+            None => Err(UbusCallError::Failed(SourceError::Parse(format!("{}.{} returned no status", object, method)))),
+        }
+    }
+
+    fn get_location(&self, device: &str, ssid: &str) -> Option<&str> {
+        self.locations.iter()
+            .find(|location| {
+                location.device.as_deref() == Some(device) || (!ssid.is_empty() && location.ssid.as_deref() == Some(ssid))
+            })
+            .map(|location| location.location.as_str())
+    }
+}
+
+fn get_or_insert_client<'a>(clients: &'a mut HashMap<String, DeviceLocation>, mac: &str) -> &'a mut DeviceLocation {
+    clients.entry(mac.to_lowercase()).or_insert_with(|| DeviceLocation {
+        device_mac: mac.to_lowercase(),
+        ..Default::default()
+    })
+}
@@ -1,8 +1,10 @@
 use async_trait::async_trait;
+use reqwest::StatusCode;
 use serde::Deserialize;
 
 use crate::{DeviceLocation, Pollable};
 use crate::device::OPNSense;
+use crate::error::SourceError;
 
 #[derive(Deserialize)]
 struct ArpEntry {
@@ -12,36 +14,27 @@ struct ArpEntry {
 
 #[async_trait]
 impl Pollable for OPNSense {
-    async fn poll_device(&self) -> Vec<DeviceLocation> {
-        let body = self.client
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let response = self.client
             .get(&self.data_url)
             .basic_auth(&self.api_key, Some(&self.api_secret))
             .send()
-            .await;
+            .await?;
 
-        if body.is_err() {
-            println!("{}", body.unwrap_err().to_string());
-            return Vec::new();
+        if response.status() == StatusCode::UNAUTHORIZED || response.status() == StatusCode::FORBIDDEN {
+            return Err(SourceError::Auth(format!("api key rejected with status {}", response.status())));
         }
-        let body = body.unwrap().text().await;
-        if body.is_err() {
-            println!("{}", body.unwrap_err().to_string());
-            return Vec::new();
-        }
-        let body = body.unwrap();
 
-        let mut entries: Vec<ArpEntry> = serde_json::from_str(&body).unwrap();
+        let body = response.text().await?;
+        let entries: Vec<ArpEntry> = serde_json::from_str(&body)?;
 
-        entries.iter_mut().map(|entry| {
+        Ok(entries.iter().map(|entry| {
             DeviceLocation {
                 ipv4: entry.ip.to_string(),
-                ipv6: Vec::new(),
                 device_mac: entry.mac.to_string(),
-                remote_mac: String::new(),
-                remote_ip: String::new(),
-                location: String::new(),
+                ..Default::default()
             }
-        }).collect()
+        }).collect())
     }
 }
 
@@ -53,8 +46,7 @@ impl OPNSense {
             .unwrap();
 
         OPNSense {
-            ip: ip.to_string(),
-            data_url: format!("https://{}/api/diagnostics/interface/getArp", ip.to_string()),
+            data_url: format!("https://{}/api/diagnostics/interface/getArp", ip),
             api_key: key.to_string(),
             api_secret: secret.to_string(),
             client,
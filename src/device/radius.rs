@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use md5::{Digest, Md5};
+use tokio::net::UdpSocket;
+
+use crate::{DeviceLocation, Pollable, UpdateSender};
+use crate::device::{normalize_mac, RadiusAccounting, RadiusSession, TrackedClient};
+use crate::error::SourceError;
+
+const ACCOUNTING_REQUEST: u8 = 4;
+const ACCOUNTING_RESPONSE: u8 = 5;
+const HEADER_LENGTH: usize = 20;
+
+const USER_NAME: u8 = 1;
+const NAS_IP_ADDRESS: u8 = 4;
+const FRAMED_IP_ADDRESS: u8 = 8;
+const CALLED_STATION_ID: u8 = 30;
+const CALLING_STATION_ID: u8 = 31;
+const NAS_IDENTIFIER: u8 = 32;
+const ACCT_STATUS_TYPE: u8 = 40;
+const ACCT_SESSION_ID: u8 = 44;
+
+const STATUS_START: u32 = 1;
+const STATUS_STOP: u32 = 2;
+const STATUS_INTERIM_UPDATE: u32 = 3;
+
+struct AccountingRequest {
+    identifier: u8,
+    authenticator: [u8; 16],
+    attributes: Vec<(u8, Vec<u8>)>,
+}
+
+#[async_trait]
+impl Pollable for RadiusAccounting {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        if let Some(error) = self.bind_error.lock().unwrap().as_ref() {
+            return Err(SourceError::Io(error.to_string()));
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+
+        // sessions without a stop packet, e.g. after a NAS reboot, disappear after the timeout
+        sessions.retain(|_, session| session.tracked.updated.elapsed() < self.session_timeout);
+        Ok(sessions.values().map(|session| session.tracked.client.clone()).collect())
+    }
+}
+
+impl RadiusAccounting {
+    pub fn new(bind: &str, secret: &str, nas: HashMap<String, String>, session_timeout: Duration, updates: UpdateSender) -> RadiusAccounting {
+        let sessions: Arc<Mutex<HashMap<String, RadiusSession>>> = Default::default();
+        let bind_error: Arc<Mutex<Option<String>>> = Default::default();
+
+        let listener = RadiusListener {
+            secret: secret.as_bytes().to_vec(),
+            nas,
+            sessions: sessions.clone(),
+            updates,
+        };
+        tokio::spawn(listener.listen(bind.to_string(), bind_error.clone()));
+
+        RadiusAccounting {
+            sessions,
+            session_timeout,
+            bind_error,
+        }
+    }
+}
+
+struct RadiusListener {
+    secret: Vec<u8>,
+    nas: HashMap<String, String>,
+    sessions: Arc<Mutex<HashMap<String, RadiusSession>>>,
+    updates: UpdateSender,
+}
+
+impl RadiusListener {
+    async fn listen(self, bind: String, bind_error: Arc<Mutex<Option<String>>>) {
+        let socket = match UdpSocket::bind(&bind).await {
+            Ok(socket) => socket,
+            Err(error) => {
+                let error = format!("failed to bind radius accounting listener to {}: {}", bind, error);
+                println!("{}", error);
+                *bind_error.lock().unwrap() = Some(error);
+                return;
+            }
+        };
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            let (length, peer) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(error) => {
+                    println!("radius accounting listener: {}", error);
+                    continue;
+                }
+            };
+
+            match self.handle_packet(&buffer[..length]) {
+                Ok(response) => {
+                    let _ = socket.send_to(&response, peer).await;
+                }
+                Err(error) => println!("radius accounting: dropping packet from {}: {}", peer.ip(), error),
+            }
+        }
+    }
+
+    /// Verifies the request, updates the session table and returns the accounting response
+    fn handle_packet(&self, packet: &[u8]) -> Result<Vec<u8>, String> {
+        let request = parse_request(packet)?;
+
+        let mut expected = Md5::new();
+        expected.update(&packet[..4]);
+        expected.update([0u8; 16]);
+        expected.update(&packet[HEADER_LENGTH..]);
+        expected.update(&self.secret);
+        if expected.finalize().as_slice() != request.authenticator {
+            return Err("invalid request authenticator, check the shared secret".to_string());
+        }
+
+        self.update_session(&request);
+
+        let mut response = vec![ACCOUNTING_RESPONSE, request.identifier, 0, HEADER_LENGTH as u8];
+        let mut authenticator = Md5::new();
+        authenticator.update(&response);
+        authenticator.update(request.authenticator);
+        authenticator.update(&self.secret);
+        response.extend_from_slice(authenticator.finalize().as_slice());
+
+        Ok(response)
+    }
+
+    fn update_session(&self, request: &AccountingRequest) {
+        let Some(mac) = request.string(CALLING_STATION_ID).and_then(|mac| normalize_mac(&mac)) else {
+            return;
+        };
+
+        let status = request.attribute(ACCT_STATUS_TYPE)
+            .and_then(|value| value.try_into().ok())
+            .map(u32::from_be_bytes);
+
+        let nas_ip = request.ipv4(NAS_IP_ADDRESS).unwrap_or_default();
+        let nas_identifier = request.string(NAS_IDENTIFIER).unwrap_or_default();
+        // a client roaming between NAS has overlapping sessions, the stop of the old one must not remove it
+        let session_id = format!(
+            "{}/{}",
+            if nas_identifier.is_empty() { &nas_ip } else { &nas_identifier },
+            request.string(ACCT_SESSION_ID).unwrap_or_default(),
+        );
+
+        let mut sessions = self.sessions.lock().unwrap();
+        match status {
+            Some(STATUS_START) | Some(STATUS_INTERIM_UPDATE) => {
+                // wireless NAS report `<ap mac>:<ssid>`, wired NAS often only their own mac address
+                let called_station = request.string(CALLED_STATION_ID)
+                    .and_then(|called_station| normalize_mac(called_station.get(..17).unwrap_or(&called_station)))
+                    .unwrap_or_default();
+
+                let location = [&nas_identifier, &called_station, &nas_ip].into_iter()
+                    .filter(|key| !key.is_empty())
+                    .find_map(|key| self.nas.get(&key.to_lowercase()))
+                    .cloned()
+                    .unwrap_or_default();
+
+                let client = DeviceLocation {
+                    ipv4: request.ipv4(FRAMED_IP_ADDRESS).unwrap_or_default(),
+                    device_mac: mac.to_string(),
+                    user: request.string(USER_NAME).unwrap_or_default(),
+                    remote_ip: nas_ip,
+                    remote_mac: called_station,
+                    location,
+                    ..Default::default()
+                };
+
+                sessions.insert(mac, RadiusSession {
+                    id: session_id,
+                    tracked: TrackedClient {
+                        client: client.clone(),
+                        updated: Instant::now(),
+                    },
+                });
+                self.updates.upsert(client);
+            }
+            Some(STATUS_STOP) if sessions.get(&mac).is_some_and(|session| session.id == session_id) => {
+                sessions.remove(&mac);
+                self.updates.remove(&mac);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl AccountingRequest {
+    fn attribute(&self, attribute_type: u8) -> Option<&[u8]> {
+        self.attributes.iter()
+            .find(|(current_type, _)| *current_type == attribute_type)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    fn string(&self, attribute_type: u8) -> Option<String> {
+        self.attribute(attribute_type).map(|value| String::from_utf8_lossy(value).trim().to_string())
+    }
+
+    fn ipv4(&self, attribute_type: u8) -> Option<String> {
+        let value: [u8; 4] = self.attribute(attribute_type)?.try_into().ok()?;
+        Some(Ipv4Addr::from(value).to_string())
+    }
+}
+
+fn parse_request(packet: &[u8]) -> Result<AccountingRequest, String> {
+    if packet.len() < HEADER_LENGTH {
+        return Err("packet too short".to_string());
+    }
+    if packet[0] != ACCOUNTING_REQUEST {
+        return Err(format!("unsupported packet code {}", packet[0]));
+    }
+
+    let length = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    if length < HEADER_LENGTH || length > packet.len() {
+        return Err("invalid packet length".to_string());
+    }
+
+    let mut attributes = Vec::new();
+    let mut offset = HEADER_LENGTH;
+    while offset + 2 <= length {
+        let attribute_length = packet[offset + 1] as usize;
+        if attribute_length < 2 || offset + attribute_length > length {
+            return Err("invalid attribute length".to_string());
+        }
+
+        attributes.push((packet[offset], packet[offset + 2..offset + attribute_length].to_vec()));
+        offset += attribute_length;
+    }
+
+    Ok(AccountingRequest {
+        identifier: packet[1],
+        authenticator: packet[4..HEADER_LENGTH].try_into().unwrap(),
+        attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    use super::*;
+    use crate::SourceUpdate;
+
+    const SECRET: &[u8] = b"testing123";
+
+    fn attribute(attribute_type: u8, value: &[u8]) -> Vec<u8> {
+        let mut attribute = vec![attribute_type, value.len() as u8 + 2];
+        attribute.extend_from_slice(value);
+        attribute
+    }
+
+    /// An Accounting-Request as sent by a NAS, signed with `secret`
+    fn request(secret: &[u8], status: u32, session_id: &str, nas: &str) -> Vec<u8> {
+        let attributes = [
+            attribute(ACCT_STATUS_TYPE, &status.to_be_bytes()),
+            attribute(ACCT_SESSION_ID, session_id.as_bytes()),
+            attribute(USER_NAME, b"alice"),
+            attribute(NAS_IP_ADDRESS, &[10, 0, 0, 2]),
+            attribute(NAS_IDENTIFIER, nas.as_bytes()),
+            attribute(CALLED_STATION_ID, b"AA-BB-CC-DD-EE-01:office"),
+            attribute(CALLING_STATION_ID, b"00-11-22-33-44-55"),
+            attribute(FRAMED_IP_ADDRESS, &[192, 168, 1, 10]),
+        ].concat();
+
+        let length = (HEADER_LENGTH + attributes.len()) as u16;
+        let mut packet = vec![ACCOUNTING_REQUEST, 7];
+        packet.extend_from_slice(&length.to_be_bytes());
+
+        let mut authenticator = Md5::new();
+        authenticator.update(&packet);
+        authenticator.update([0u8; 16]);
+        authenticator.update(&attributes);
+        authenticator.update(secret);
+        packet.extend_from_slice(authenticator.finalize().as_slice());
+        packet.extend_from_slice(&attributes);
+        packet
+    }
+
+    fn listener() -> (RadiusListener, UnboundedReceiver<(String, SourceUpdate)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let listener = RadiusListener {
+            secret: SECRET.to_vec(),
+            nas: HashMap::from([("ap-office".to_string(), "Office".to_string())]),
+            sessions: Default::default(),
+            updates: UpdateSender {
+                source: "radius".to_string(),
+                sender,
+            },
+        };
+
+        (listener, receiver)
+    }
+
+    #[test]
+    fn parses_attributes() {
+        let request = parse_request(&request(SECRET, STATUS_START, "5F0A1B2C", "ap-office")).unwrap();
+
+        assert_eq!(request.identifier, 7);
+        assert_eq!(request.string(USER_NAME).as_deref(), Some("alice"));
+        assert_eq!(request.string(ACCT_SESSION_ID).as_deref(), Some("5F0A1B2C"));
+        assert_eq!(request.ipv4(NAS_IP_ADDRESS).as_deref(), Some("10.0.0.2"));
+        assert_eq!(request.ipv4(FRAMED_IP_ADDRESS).as_deref(), Some("192.168.1.10"));
+        assert_eq!(request.ipv4(USER_NAME), None);
+        assert_eq!(request.attribute(ACCT_STATUS_TYPE), Some(&STATUS_START.to_be_bytes()[..]));
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let packet = request(SECRET, STATUS_START, "5F0A1B2C", "ap-office");
+
+        assert!(parse_request(&packet[..HEADER_LENGTH - 1]).is_err());
+        // the length field claims more than was received
+        assert!(parse_request(&packet[..packet.len() - 4]).is_err());
+
+        let mut access_request = packet.clone();
+        access_request[0] = 1;
+        assert!(parse_request(&access_request).is_err());
+
+        let mut zero_length_attribute = packet.clone();
+        zero_length_attribute[HEADER_LENGTH + 1] = 0;
+        assert!(parse_request(&zero_length_attribute).is_err());
+
+        let mut overlong_attribute = packet;
+        let last = overlong_attribute.len() - 5;
+        overlong_attribute[last] = 10;
+        assert!(parse_request(&overlong_attribute).is_err());
+    }
+
+    #[test]
+    fn verifies_the_request_authenticator() {
+        let (listener, _receiver) = listener();
+
+        assert!(listener.handle_packet(&request(b"wrong", STATUS_START, "5F0A1B2C", "ap-office")).is_err());
+
+        let mut tampered = request(SECRET, STATUS_START, "5F0A1B2C", "ap-office");
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(listener.handle_packet(&tampered).is_err());
+        assert!(listener.sessions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn signs_the_response() {
+        let (listener, _receiver) = listener();
+        let packet = request(SECRET, STATUS_START, "5F0A1B2C", "ap-office");
+        let response = listener.handle_packet(&packet).unwrap();
+
+        assert_eq!(&response[..4], &[ACCOUNTING_RESPONSE, 7, 0, HEADER_LENGTH as u8]);
+
+        let mut expected = Md5::new();
+        expected.update(&response[..4]);
+        expected.update(&packet[4..HEADER_LENGTH]);
+        expected.update(SECRET);
+        assert_eq!(&response[4..], expected.finalize().as_slice());
+    }
+
+    #[test]
+    fn tracks_sessions() {
+        let (listener, mut receiver) = listener();
+        listener.handle_packet(&request(SECRET, STATUS_START, "5F0A1B2C", "ap-office")).unwrap();
+
+        let sessions = listener.sessions.lock().unwrap();
+        let client = &sessions["00:11:22:33:44:55"].tracked.client;
+        assert_eq!(client.ipv4, "192.168.1.10");
+        assert_eq!(client.user, "alice");
+        assert_eq!(client.remote_ip, "10.0.0.2");
+        assert_eq!(client.remote_mac, "aa:bb:cc:dd:ee:01");
+        assert_eq!(client.location, "Office");
+        drop(sessions);
+
+        assert!(matches!(receiver.try_recv(), Ok((_, SourceUpdate::Upsert(_)))));
+    }
+
+    #[test]
+    fn only_the_current_session_ends_a_client() {
+        let (listener, mut receiver) = listener();
+        listener.handle_packet(&request(SECRET, STATUS_START, "1", "ap-office")).unwrap();
+        // the client roamed, the stop of its old session arrives after the start of the new one
+        listener.handle_packet(&request(SECRET, STATUS_START, "2", "ap-hallway")).unwrap();
+        listener.handle_packet(&request(SECRET, STATUS_STOP, "1", "ap-office")).unwrap();
+
+        assert!(listener.sessions.lock().unwrap().contains_key("00:11:22:33:44:55"));
+
+        listener.handle_packet(&request(SECRET, STATUS_STOP, "2", "ap-hallway")).unwrap();
+        assert!(listener.sessions.lock().unwrap().is_empty());
+
+        let updates: Vec<SourceUpdate> = std::iter::from_fn(|| receiver.try_recv().ok()).map(|(_, update)| update).collect();
+        assert_eq!(updates.len(), 3);
+        assert!(matches!(&updates[2], SourceUpdate::Remove(mac) if mac == "00:11:22:33:44:55"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::{normalize_mac, RestconfMapping, RestconfSwitch};
+use crate::error::SourceError;
+
+#[async_trait]
+impl Pollable for RestconfSwitch {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let data = self.get_mac_table().await?;
+
+        let mut clients: Vec<DeviceLocation> = Vec::new();
+        for entry in select(&data, &self.mapping.entries) {
+            let Some(mac) = get_field(entry, &self.mapping.mac).and_then(|mac| normalize_mac(&mac)) else {
+                continue;
+            };
+            let interface = get_field(entry, &self.mapping.interface).unwrap_or_default();
+
+            if let Some(vlan) = self.vlan
+                && get_field(entry, &self.mapping.vlan) != Some(vlan.to_string()) {
+                continue;
+            }
+
+            if self.ports.contains(&interface) {
+                clients.push(DeviceLocation {
+                    device_mac: mac,
+                    remote_ip: self.ip.to_string(),
+                    location: self.location.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(clients)
+    }
+
+    fn address(&self) -> Option<IpAddr> {
+        self.ip.parse().ok()
+    }
+}
+
+impl RestconfSwitch {
+    pub fn new(
+        ip: &str,
+        user: &str,
+        password: &str,
+        ports: Vec<String>,
+        location: &str,
+        vlan: Option<u16>,
+        mapping: RestconfMapping,
+    ) -> RestconfSwitch {
+        let client = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+
+        RestconfSwitch {
+            ip: ip.to_string(),
+            ports,
+            location: location.to_string(),
+            vlan,
+            data_url: format!("https://{}/restconf/{}", ip, mapping.path.trim_start_matches('/')),
+            mapping,
+            user: user.to_string(),
+            password: password.to_string(),
+            client,
+        }
+    }
+
+    async fn get_mac_table(&self) -> Result<Value, SourceError> {
+        let response = self.client
+            .get(&self.data_url)
+            .basic_auth(&self.user, Some(&self.password))
+            .header("Accept", "application/yang-data+json")
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => return Err(SourceError::Auth("invalid credentials".to_string())),
+            status => return Err(SourceError::Network(format!("request failed with status {}", status))),
+        }
+
+        let body = response.text().await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+/// Walks the container names of `path`, lists along the way are flattened into their entries
+fn select<'a>(value: &'a Value, path: &[String]) -> Vec<&'a Value> {
+    if let Value::Array(items) = value {
+        return items.iter().flat_map(|item| select(item, path)).collect();
+    }
+
+    match path.split_first() {
+        Some((name, rest)) => value.get(name).map(|child| select(child, rest)).unwrap_or_default(),
+        None => vec![value],
+    }
+}
+
+fn get_field(entry: &Value, path: &[String]) -> Option<String> {
+    match path.iter().try_fold(entry, |value, name| value.get(name))? {
+        Value::String(value) => Some(value.to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,583 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::net::UdpSocket;
+
+use crate::{DeviceLocation, Pollable, PollTriggers, UpdateSender};
+use crate::device::{SnmpTrapClient, SnmpTrapReceiver, SnmpTrapSwitch, TrackedClient};
+use crate::error::SourceError;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_TRAP_V1: u8 = 0xa4;
+const TAG_TRAP_V2: u8 = 0xa7;
+
+const SNMP_TRAP_OID: &str = "1.3.6.1.6.3.1.1.4.1.0";
+const LINK_DOWN: &str = "1.3.6.1.6.3.1.1.5.3";
+const LINK_UP: &str = "1.3.6.1.6.3.1.1.5.4";
+const CISCO_MAC_CHANGED: &str = "1.3.6.1.4.1.9.9.215.2.0.1";
+const CISCO_MAC_CHANGED_MSG: &str = "1.3.6.1.4.1.9.9.215.1.1.8.1.2";
+
+const MAC_LEARNED: u8 = 1;
+const MAC_REMOVED: u8 = 2;
+
+struct Trap {
+    community: String,
+    agent: Option<IpAddr>,
+    trap_oid: String,
+    varbinds: Vec<(String, Vec<u8>)>,
+}
+
+/// A minimal BER reader, sufficient for SNMPv1 and SNMPv2c trap PDUs
+struct BerReader<'a> {
+    data: &'a [u8],
+}
+
+#[async_trait]
+impl Pollable for SnmpTrapReceiver {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        if let Some(error) = self.bind_error.lock().unwrap().as_ref() {
+            return Err(SourceError::Io(error.to_string()));
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+
+        clients.retain(|_, client| client.tracked.updated.elapsed() < self.client_timeout);
+        Ok(clients.values().map(|client| client.tracked.client.clone()).collect())
+    }
+}
+
+impl SnmpTrapReceiver {
+    pub fn new(bind: &str, community: &str, switches: HashMap<IpAddr, SnmpTrapSwitch>, client_timeout: Duration, updates: UpdateSender, poll_triggers: PollTriggers) -> SnmpTrapReceiver {
+        let clients: Arc<Mutex<HashMap<String, SnmpTrapClient>>> = Default::default();
+        let bind_error: Arc<Mutex<Option<String>>> = Default::default();
+
+        let listener = SnmpTrapListener {
+            community: community.to_string(),
+            switches,
+            clients: clients.clone(),
+            updates,
+            poll_triggers,
+        };
+        tokio::spawn(listener.listen(bind.to_string(), bind_error.clone()));
+
+        SnmpTrapReceiver {
+            clients,
+            client_timeout,
+            bind_error,
+        }
+    }
+}
+
+struct SnmpTrapListener {
+    community: String,
+    switches: HashMap<IpAddr, SnmpTrapSwitch>,
+    clients: Arc<Mutex<HashMap<String, SnmpTrapClient>>>,
+    updates: UpdateSender,
+    poll_triggers: PollTriggers,
+}
+
+impl SnmpTrapListener {
+    async fn listen(self, bind: String, bind_error: Arc<Mutex<Option<String>>>) {
+        let socket = match UdpSocket::bind(&bind).await {
+            Ok(socket) => socket,
+            Err(error) => {
+                let error = format!("failed to bind snmp trap listener to {}: {}", bind, error);
+                println!("{}", error);
+                *bind_error.lock().unwrap() = Some(error);
+                return;
+            }
+        };
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            match socket.recv_from(&mut buffer).await {
+                Ok((length, peer)) => match parse_trap(&buffer[..length]) {
+                    Some(trap) => self.handle_trap(trap, peer.ip()),
+                    None => println!("snmp trap listener: dropping malformed trap from {}", peer.ip()),
+                },
+                Err(error) => println!("snmp trap listener: {}", error),
+            }
+        }
+    }
+
+    fn handle_trap(&self, trap: Trap, peer: IpAddr) {
+        if trap.community != self.community {
+            println!("snmp trap listener: dropping trap from {} with wrong community", peer);
+            return;
+        }
+
+        let agent = trap.agent.unwrap_or(peer);
+        let Some(switch) = self.switches.get(&agent) else {
+            return;
+        };
+
+        match trap.trap_oid.as_str() {
+            CISCO_MAC_CHANGED => {
+                for (oid, value) in trap.varbinds.iter() {
+                    if oid.starts_with(CISCO_MAC_CHANGED_MSG) {
+                        self.apply_mac_changes(&agent, switch, value);
+                    }
+                }
+            }
+            // link changes are not reported per client, so the sources polling the switch are polled right away instead
+            LINK_DOWN | LINK_UP => self.poll_triggers.trigger(&agent),
+            _ => {}
+        }
+    }
+
+    /// cmnHistMacChangedMsg contains 11 byte records: operation, vlan (2), mac (6), dot1dBasePort (2)
+    fn apply_mac_changes(&self, agent: &IpAddr, switch: &SnmpTrapSwitch, message: &[u8]) {
+        let mut clients = self.clients.lock().unwrap();
+
+        for record in message.chunks_exact(11) {
+            let mac = record[3..9].iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<String>>()
+                .join(":");
+            let port = u16::from_be_bytes([record[9], record[10]]).to_string();
+
+            match record[0] {
+                MAC_LEARNED if switch.ports.contains(&port) => {
+                    let client = DeviceLocation {
+                        device_mac: mac.to_string(),
+                        remote_ip: agent.to_string(),
+                        location: switch.location.to_string(),
+                        ..Default::default()
+                    };
+
+                    clients.insert(mac, SnmpTrapClient {
+                        agent: *agent,
+                        port,
+                        tracked: TrackedClient {
+                            client: client.clone(),
+                            updated: Instant::now(),
+                        },
+                    });
+                    self.updates.upsert(client);
+                }
+                MAC_LEARNED => {}
+                // a client that moved on is still connected, the removal from where it was before is late
+                MAC_REMOVED if clients.get(&mac).is_some_and(|client| client.agent == *agent && client.port == port) => {
+                    clients.remove(&mac);
+                    self.updates.remove(&mac);
+                }
+                MAC_REMOVED => {}
+                _ => break,
+            }
+        }
+    }
+}
+
+fn parse_trap(packet: &[u8]) -> Option<Trap> {
+    let mut message = BerReader::new(packet).read(TAG_SEQUENCE)?;
+    message.read(TAG_INTEGER)?;
+    let community = String::from_utf8_lossy(message.read(TAG_OCTET_STRING)?.data).to_string();
+
+    let (tag, mut pdu) = message.read_any()?;
+    match tag {
+        TAG_TRAP_V1 => {
+            let enterprise = decode_oid(pdu.read(TAG_OID)?.data)?;
+            let agent: [u8; 4] = pdu.read(TAG_IP_ADDRESS)?.data.try_into().ok()?;
+            let generic = decode_integer(pdu.read(TAG_INTEGER)?.data);
+            let specific = decode_integer(pdu.read(TAG_INTEGER)?.data);
+            pdu.read_any()?;
+
+            // generic traps map to the SNMPv2 trap oids, enterprise specific traps to `<enterprise>.0.<specific>`
+            let trap_oid = if generic == 6 {
+                format!("{}.0.{}", enterprise, specific)
+            } else {
+                format!("1.3.6.1.6.3.1.1.5.{}", generic + 1)
+            };
+
+            Some(Trap {
+                community,
+                agent: Some(IpAddr::V4(Ipv4Addr::from(agent))),
+                trap_oid,
+                varbinds: read_varbinds(pdu.read(TAG_SEQUENCE)?)?,
+            })
+        }
+        TAG_TRAP_V2 => {
+            pdu.read(TAG_INTEGER)?;
+            pdu.read(TAG_INTEGER)?;
+            pdu.read(TAG_INTEGER)?;
+            let varbinds = read_varbinds(pdu.read(TAG_SEQUENCE)?)?;
+
+            let trap_oid = varbinds.iter()
+                .find(|(oid, _)| oid == SNMP_TRAP_OID)
+                .and_then(|(_, value)| decode_oid(value))?;
+
+            Some(Trap {
+                community,
+                agent: None,
+                trap_oid,
+                varbinds,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Returns the oid and the raw value of every variable binding, oid values are stored encoded
+fn read_varbinds(mut varbinds: BerReader) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut result = Vec::new();
+
+    while !varbinds.data.is_empty() {
+        let mut varbind = varbinds.read(TAG_SEQUENCE)?;
+        let oid = decode_oid(varbind.read(TAG_OID)?.data)?;
+        let (_, value) = varbind.read_any()?;
+
+        result.push((oid, value.data.to_vec()));
+    }
+
+    Some(result)
+}
+
+fn decode_oid(data: &[u8]) -> Option<String> {
+    let (first, rest) = data.split_first()?;
+    let mut parts = vec![(first / 40) as u64, (first % 40) as u64];
+
+    let mut value: u64 = 0;
+    for byte in rest {
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            parts.push(value);
+            value = 0;
+        }
+    }
+
+    // the last sub-identifier of a truncated oid still has its continuation bit set
+    if rest.last().is_some_and(|byte| byte & 0x80 != 0) {
+        return None;
+    }
+
+    Some(parts.iter().map(u64::to_string).collect::<Vec<String>>().join("."))
+}
+
+fn decode_integer(data: &[u8]) -> i64 {
+    data.iter().fold(if data.first().is_some_and(|byte| byte & 0x80 != 0) { -1 } else { 0 }, |value, byte| (value << 8) | *byte as i64)
+}
+
+impl<'a> BerReader<'a> {
+    fn new(data: &'a [u8]) -> BerReader<'a> {
+        BerReader {
+            data,
+        }
+    }
+
+    fn read(&mut self, expected_tag: u8) -> Option<BerReader<'a>> {
+        match self.read_any()? {
+            (tag, value) if tag == expected_tag => Some(value),
+            _ => None,
+        }
+    }
+
+    fn read_any(&mut self) -> Option<(u8, BerReader<'a>)> {
+        let (&tag, rest) = self.data.split_first()?;
+        let (&length_byte, rest) = rest.split_first()?;
+
+        let (length, rest) = if length_byte & 0x80 == 0 {
+            (length_byte as usize, rest)
+        } else {
+            let length_bytes = (length_byte & 0x7f) as usize;
+            if length_bytes == 0 || length_bytes > 4 || rest.len() < length_bytes {
+                return None;
+            }
+
+            let length = rest[..length_bytes].iter().fold(0usize, |length, byte| (length << 8) | *byte as usize);
+            (length, &rest[length_bytes..])
+        };
+
+        if rest.len() < length {
+            return None;
+        }
+
+        self.data = &rest[length..];
+        Some((tag, BerReader::new(&rest[..length])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+    use tokio::sync::Notify;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    use super::*;
+    use crate::SourceUpdate;
+
+    const TAG_NULL: u8 = 0x05;
+    const TAG_TIMETICKS: u8 = 0x43;
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        if content.len() < 0x80 {
+            encoded.push(content.len() as u8);
+        } else {
+            encoded.extend_from_slice(&[0x82, (content.len() >> 8) as u8, content.len() as u8]);
+        }
+        encoded.extend_from_slice(content);
+        encoded
+    }
+
+    fn oid(oid: &str) -> Vec<u8> {
+        let parts: Vec<u64> = oid.split('.').map(|part| part.parse().unwrap()).collect();
+        let mut encoded = vec![(parts[0] * 40 + parts[1]) as u8];
+        for part in &parts[2..] {
+            let mut bytes = vec![(part & 0x7f) as u8];
+            let mut rest = part >> 7;
+            while rest > 0 {
+                bytes.insert(0, (rest & 0x7f) as u8 | 0x80);
+                rest >>= 7;
+            }
+            encoded.extend(bytes);
+        }
+        tlv(TAG_OID, &encoded)
+    }
+
+    fn varbind(name: &str, value: Vec<u8>) -> Vec<u8> {
+        tlv(TAG_SEQUENCE, &[oid(name), value].concat())
+    }
+
+    fn message(version: u8, community: &str, pdu: Vec<u8>) -> Vec<u8> {
+        tlv(TAG_SEQUENCE, &[tlv(TAG_INTEGER, &[version]), tlv(TAG_OCTET_STRING, community.as_bytes()), pdu].concat())
+    }
+
+    fn trap_v2(community: &str, trap_oid: &str, varbinds: Vec<Vec<u8>>) -> Vec<u8> {
+        let varbinds = [
+            vec![varbind("1.3.6.1.2.1.1.3.0", tlv(TAG_TIMETICKS, &[0x01, 0x2c])), varbind(SNMP_TRAP_OID, oid(trap_oid))],
+            varbinds,
+        ].concat().concat();
+
+        message(1, community, tlv(TAG_TRAP_V2, &[
+            tlv(TAG_INTEGER, &[0x12, 0x34]),
+            tlv(TAG_INTEGER, &[0]),
+            tlv(TAG_INTEGER, &[0]),
+            tlv(TAG_SEQUENCE, &varbinds),
+        ].concat()))
+    }
+
+    fn trap_v1(community: &str, enterprise: &str, agent: [u8; 4], generic: u8, specific: u8, varbinds: Vec<Vec<u8>>) -> Vec<u8> {
+        message(0, community, tlv(TAG_TRAP_V1, &[
+            oid(enterprise),
+            tlv(TAG_IP_ADDRESS, &agent),
+            tlv(TAG_INTEGER, &[generic]),
+            tlv(TAG_INTEGER, &[specific]),
+            tlv(TAG_TIMETICKS, &[0x01, 0x2c]),
+            tlv(TAG_SEQUENCE, &varbinds.concat()),
+        ].concat()))
+    }
+
+    /// A cmnHistMacChangedMsg record
+    fn mac_change(operation: u8, mac: [u8; 6], port: u16) -> Vec<u8> {
+        [&[operation, 0x00, 0x17][..], &mac, &port.to_be_bytes()].concat()
+    }
+
+    fn listener() -> (SnmpTrapListener, UnboundedReceiver<(String, SourceUpdate)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let listener = SnmpTrapListener {
+            community: "public".to_string(),
+            switches: HashMap::from([
+                ("10.0.0.2".parse().unwrap(), SnmpTrapSwitch {
+                    ports: vec!["5".to_string(), "6".to_string()],
+                    location: "Office".to_string(),
+                }),
+                ("10.0.0.3".parse().unwrap(), SnmpTrapSwitch {
+                    ports: vec!["5".to_string()],
+                    location: "Hallway".to_string(),
+                }),
+            ]),
+            clients: Default::default(),
+            updates: UpdateSender {
+                source: "snmp_traps".to_string(),
+                sender,
+            },
+            poll_triggers: PollTriggers::default(),
+        };
+
+        (listener, receiver)
+    }
+
+    #[test]
+    fn decodes_oids() {
+        assert_eq!(decode_oid(&oid("1.3.6.1.4.1.9.9.215.2.0.1")[2..]).as_deref(), Some("1.3.6.1.4.1.9.9.215.2.0.1"));
+        assert_eq!(decode_oid(&oid("1.3.6.1.4.1.311.21.20")[2..]).as_deref(), Some("1.3.6.1.4.1.311.21.20"));
+        assert_eq!(decode_oid(&[]), None);
+        assert_eq!(decode_oid(&[0x2b, 0x06, 0x82]), None);
+    }
+
+    #[test]
+    fn decodes_integers() {
+        assert_eq!(decode_integer(&[0x00]), 0);
+        assert_eq!(decode_integer(&[0x7f]), 127);
+        assert_eq!(decode_integer(&[0x00, 0x80]), 128);
+        assert_eq!(decode_integer(&[0xff]), -1);
+        assert_eq!(decode_integer(&[0xff, 0x7f]), -129);
+        assert_eq!(decode_integer(&[]), 0);
+    }
+
+    #[test]
+    fn reads_long_form_lengths() {
+        let content = vec![0x61; 300];
+        let encoded = tlv(TAG_OCTET_STRING, &content);
+        let mut reader = BerReader::new(&encoded);
+
+        assert_eq!(reader.read(TAG_OCTET_STRING).unwrap().data, &content[..]);
+        assert!(reader.data.is_empty());
+        assert!(BerReader::new(&[TAG_OCTET_STRING, 0x80]).read_any().is_none());
+        assert!(BerReader::new(&[TAG_OCTET_STRING, 0x85, 1, 0, 0, 0, 0]).read_any().is_none());
+        assert!(BerReader::new(&[TAG_OCTET_STRING, 0x82, 0x01]).read_any().is_none());
+        assert!(BerReader::new(&[TAG_NULL, 0x00]).read(TAG_INTEGER).is_none());
+    }
+
+    #[test]
+    fn parses_v2c_traps() {
+        let packet = trap_v2("public", LINK_DOWN, vec![varbind("1.3.6.1.2.1.2.2.1.1.5", tlv(TAG_INTEGER, &[5]))]);
+        let trap = parse_trap(&packet).unwrap();
+
+        assert_eq!(trap.community, "public");
+        assert_eq!(trap.agent, None);
+        assert_eq!(trap.trap_oid, LINK_DOWN);
+        assert_eq!(trap.varbinds.len(), 3);
+        assert_eq!(trap.varbinds[2], ("1.3.6.1.2.1.2.2.1.1.5".to_string(), vec![5]));
+    }
+
+    #[test]
+    fn parses_v1_traps() {
+        let packet = trap_v1("public", "1.3.6.1.4.1.9.9.215.2", [10, 0, 0, 2], 6, 1, vec![
+            varbind(&format!("{}.1", CISCO_MAC_CHANGED_MSG), tlv(TAG_OCTET_STRING, &mac_change(MAC_LEARNED, [0, 0x11, 0x22, 0x33, 0x44, 0x55], 5))),
+        ]);
+        let trap = parse_trap(&packet).unwrap();
+
+        assert_eq!(trap.agent, Some("10.0.0.2".parse().unwrap()));
+        assert_eq!(trap.trap_oid, CISCO_MAC_CHANGED);
+        assert_eq!(trap.varbinds.len(), 1);
+
+        let link_up = trap_v1("public", "1.3.6.1.4.1.11", [10, 0, 0, 2], 3, 0, vec![]);
+        assert_eq!(parse_trap(&link_up).unwrap().trap_oid, LINK_UP);
+    }
+
+    #[test]
+    fn rejects_malformed_traps() {
+        let packet = trap_v2("public", LINK_DOWN, vec![]);
+
+        for length in [0, 1, 10, packet.len() / 2, packet.len() - 1] {
+            assert!(parse_trap(&packet[..length]).is_none(), "truncated to {} bytes", length);
+        }
+
+        // a get request instead of a trap
+        let mut get_request = packet.clone();
+        let pdu = get_request.iter().position(|byte| *byte == TAG_TRAP_V2).unwrap();
+        get_request[pdu] = 0xa0;
+        assert!(parse_trap(&get_request).is_none());
+
+        // a v2c trap without snmpTrapOID
+        let varbinds = varbind("1.3.6.1.2.1.1.3.0", tlv(TAG_TIMETICKS, &[0x01]));
+        let without_trap_oid = message(1, "public", tlv(TAG_TRAP_V2, &[
+            tlv(TAG_INTEGER, &[1]),
+            tlv(TAG_INTEGER, &[0]),
+            tlv(TAG_INTEGER, &[0]),
+            tlv(TAG_SEQUENCE, &varbinds),
+        ].concat()));
+        assert!(parse_trap(&without_trap_oid).is_none());
+    }
+
+    #[test]
+    fn applies_mac_changes() {
+        let (listener, mut receiver) = listener();
+        let message = [
+            mac_change(MAC_LEARNED, [0, 0x11, 0x22, 0x33, 0x44, 0x55], 5),
+            mac_change(MAC_LEARNED, [0, 0x11, 0x22, 0x33, 0x44, 0x66], 24),
+            mac_change(MAC_LEARNED, [0, 0x11, 0x22, 0x33, 0x44, 0x77], 6),
+            mac_change(MAC_REMOVED, [0, 0x11, 0x22, 0x33, 0x44, 0x77], 6),
+        ].concat();
+        let packet = trap_v1("public", "1.3.6.1.4.1.9.9.215.2", [10, 0, 0, 2], 6, 1, vec![
+            varbind(&format!("{}.1", CISCO_MAC_CHANGED_MSG), tlv(TAG_OCTET_STRING, &message)),
+        ]);
+
+        listener.handle_trap(parse_trap(&packet).unwrap(), "10.0.0.2".parse().unwrap());
+
+        let clients = listener.clients.lock().unwrap();
+        assert_eq!(clients.len(), 1);
+        let client = &clients["00:11:22:33:44:55"].tracked.client;
+        assert_eq!(client.remote_ip, "10.0.0.2");
+        assert_eq!(client.location, "Office");
+        drop(clients);
+
+        let updates: Vec<SourceUpdate> = std::iter::from_fn(|| receiver.try_recv().ok()).map(|(_, update)| update).collect();
+        assert_eq!(updates.len(), 3);
+    }
+
+    fn mac_change_trap(agent: [u8; 4], records: Vec<Vec<u8>>) -> Trap {
+        let packet = trap_v1("public", "1.3.6.1.4.1.9.9.215.2", agent, 6, 1, vec![
+            varbind(&format!("{}.1", CISCO_MAC_CHANGED_MSG), tlv(TAG_OCTET_STRING, &records.concat())),
+        ]);
+        parse_trap(&packet).unwrap()
+    }
+
+    #[test]
+    fn late_removal_from_the_previous_switch_keeps_the_client() {
+        let (listener, mut receiver) = listener();
+        let mac = [0, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+        listener.handle_trap(mac_change_trap([10, 0, 0, 2], vec![mac_change(MAC_LEARNED, mac, 5)]), "10.0.0.2".parse().unwrap());
+        listener.handle_trap(mac_change_trap([10, 0, 0, 3], vec![mac_change(MAC_LEARNED, mac, 5)]), "10.0.0.3".parse().unwrap());
+        // removals from another port of the current switch or from the old switch are late
+        listener.handle_trap(mac_change_trap([10, 0, 0, 3], vec![mac_change(MAC_REMOVED, mac, 6)]), "10.0.0.3".parse().unwrap());
+        listener.handle_trap(mac_change_trap([10, 0, 0, 2], vec![mac_change(MAC_REMOVED, mac, 5)]), "10.0.0.2".parse().unwrap());
+
+        let clients = listener.clients.lock().unwrap();
+        let client = &clients["00:11:22:33:44:55"];
+        assert_eq!(client.agent, "10.0.0.3".parse::<IpAddr>().unwrap());
+        assert_eq!(client.tracked.client.location, "Hallway");
+        drop(clients);
+
+        listener.handle_trap(mac_change_trap([10, 0, 0, 3], vec![mac_change(MAC_REMOVED, mac, 5)]), "10.0.0.3".parse().unwrap());
+        assert!(listener.clients.lock().unwrap().is_empty());
+
+        let updates: Vec<SourceUpdate> = std::iter::from_fn(|| receiver.try_recv().ok()).map(|(_, update)| update).collect();
+        assert_eq!(updates.len(), 3);
+        assert!(matches!(&updates[2], SourceUpdate::Remove(mac) if mac == "00:11:22:33:44:55"));
+    }
+
+    #[test]
+    fn ignores_incomplete_mac_change_records() {
+        let (listener, _receiver) = listener();
+        let message = mac_change(MAC_LEARNED, [0, 0x11, 0x22, 0x33, 0x44, 0x55], 5);
+
+        listener.apply_mac_changes(&"10.0.0.2".parse().unwrap(), &listener.switches[&"10.0.0.2".parse().unwrap()], &message[..10]);
+        assert!(listener.clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn drops_traps_with_wrong_community() {
+        let (listener, _receiver) = listener();
+        let packet = trap_v1("private", "1.3.6.1.4.1.9.9.215.2", [10, 0, 0, 2], 6, 1, vec![
+            varbind(&format!("{}.1", CISCO_MAC_CHANGED_MSG), tlv(TAG_OCTET_STRING, &mac_change(MAC_LEARNED, [0, 0x11, 0x22, 0x33, 0x44, 0x55], 5))),
+        ]);
+
+        listener.handle_trap(parse_trap(&packet).unwrap(), "10.0.0.2".parse().unwrap());
+        assert!(listener.clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn link_traps_trigger_polls_of_the_switch() {
+        let (listener, _receiver) = listener();
+        let switch = Arc::new(Notify::new());
+        let other_switch = Arc::new(Notify::new());
+        listener.poll_triggers.add("10.0.0.2".parse().unwrap(), switch.clone());
+        listener.poll_triggers.add("10.0.0.3".parse().unwrap(), other_switch.clone());
+
+        let packet = trap_v2("public", LINK_DOWN, vec![]);
+        listener.handle_trap(parse_trap(&packet).unwrap(), "10.0.0.2".parse().unwrap());
+
+        assert!(switch.notified().now_or_never().is_some());
+        assert!(other_switch.notified().now_or_never().is_none());
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use ssh2::{HashType, Session};
+use tokio::task;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::{normalize_mac, SshSwitch, TextFsm};
+use crate::error::SourceError;
+
+/// Value names used for the columns by our own and by the ntc-templates collection
+const MAC_VALUES: [&str; 3] = ["MAC", "MAC_ADDRESS", "DESTINATION_ADDRESS"];
+const VLAN_VALUES: [&str; 3] = ["VLAN", "VLAN_ID", "VLAN_NUMBER"];
+const PORT_VALUES: [&str; 4] = ["PORT", "INTERFACE", "DESTINATION_PORT", "PORTS"];
+
+#[async_trait]
+impl Pollable for SshSwitch {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let address = self.address.to_string();
+        let user = self.user.to_string();
+        let password = self.password.to_string();
+        let host_key = self.host_key.to_string();
+        let command = self.command.to_string();
+        let timeout = self.timeout;
+
+        let output = task::spawn_blocking(move || run_command(&address, &user, &password, &host_key, &command, timeout))
+            .await
+            .map_err(|error| SourceError::Io(error.to_string()))??;
+
+        let rows = self.template.run(&output).map_err(SourceError::Parse)?;
+
+        let mut clients: Vec<DeviceLocation> = Vec::new();
+        for row in rows.iter() {
+            let Some(mac) = get_column(row, &MAC_VALUES).and_then(normalize_mac) else {
+                continue;
+            };
+
+            if let Some(vlan) = self.vlan
+                && get_column(row, &VLAN_VALUES) != Some(&vlan.to_string()) {
+                continue;
+            }
+
+            // ports of a list value are joined, a client learned on one of them is enough
+            let on_port = get_column(row, &PORT_VALUES)
+                .map(|ports| ports.split(',').any(|port| self.ports.iter().any(|configured| configured == port.trim())))
+                .unwrap_or(false);
+
+            if on_port {
+                clients.push(DeviceLocation {
+                    device_mac: mac,
+                    remote_ip: self.ip.to_string(),
+                    location: self.location.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(clients)
+    }
+
+    fn address(&self) -> Option<IpAddr> {
+        self.ip.parse().ok()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+}
+
+impl SshSwitch {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        ip: &str,
+        port: u16,
+        user: &str,
+        password: &str,
+        host_key: &str,
+        command: &str,
+        template: TextFsm,
+        ports: Vec<String>,
+        location: &str,
+        vlan: Option<u16>,
+        timeout: Duration,
+    ) -> SshSwitch {
+        SshSwitch {
+            ip: ip.to_string(),
+            address: format!("{}:{}", ip, port),
+            user: user.to_string(),
+            password: password.to_string(),
+            host_key: host_key.to_string(),
+            command: command.to_string(),
+            template,
+            ports,
+            location: location.to_string(),
+            vlan,
+            timeout,
+        }
+    }
+}
+
+fn get_column<'a>(row: &'a HashMap<String, String>, names: &[&str]) -> Option<&'a str> {
+    row.iter()
+        .find(|(name, _)| names.iter().any(|candidate| name.eq_ignore_ascii_case(candidate)))
+        .map(|(_, value)| value.as_str())
+}
+
+/// The libssh2 timeout applies to every single call, so it is lowered to what is left of the overall timeout.
+/// A timeout of 0 would disable it.
+fn remaining_millis(deadline: Instant) -> Result<u32, SourceError> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(SourceError::Timeout);
+    }
+
+    Ok(remaining.as_millis().clamp(1, u32::MAX as u128) as u32)
+}
+
+/// Executes `command` over ssh and returns its output, libssh2 blocks so this runs on its own thread.
+/// The whole exchange is bounded by `timeout`.
+fn run_command(address: &str, user: &str, password: &str, host_key: &str, command: &str, timeout: Duration) -> Result<String, SourceError> {
+    let address: SocketAddr = address
+        .to_socket_addrs()
+        .map_err(|error| SourceError::Network(error.to_string()))?
+        .next()
+        .ok_or_else(|| SourceError::Network(format!("could not resolve {}", address)))?;
+
+    let deadline = Instant::now() + timeout;
+    let stream = TcpStream::connect_timeout(&address, timeout).map_err(|error| match error.kind() {
+        ErrorKind::TimedOut => SourceError::Timeout,
+        _ => SourceError::Network(error.to_string()),
+    })?;
+
+    let network_error = |error: ssh2::Error| SourceError::Network(error.to_string());
+
+    let mut session = Session::new().map_err(network_error)?;
+    session.set_timeout(remaining_millis(deadline)?);
+    session.set_tcp_stream(stream);
+    session.handshake().map_err(|error| SourceError::Network(format!("handshake failed: {}", error)))?;
+
+    // the password must not be sent to a device impersonating the switch
+    let fingerprint = session.host_key_hash(HashType::Sha256)
+        .map(|hash| STANDARD_NO_PAD.encode(hash))
+        .ok_or_else(|| SourceError::Auth("switch sent no host key".to_string()))?;
+    if fingerprint != host_key {
+        return Err(SourceError::Auth(format!("host key SHA256:{} does not match the configured host_key", fingerprint)));
+    }
+
+    session.set_timeout(remaining_millis(deadline)?);
+    session.userauth_password(user, password).map_err(|error| SourceError::Auth(error.to_string()))?;
+
+    session.set_timeout(remaining_millis(deadline)?);
+    let mut channel = session.channel_session().map_err(network_error)?;
+    channel.exec(command).map_err(network_error)?;
+
+    session.set_timeout(remaining_millis(deadline)?);
+    let mut output = String::new();
+    channel.read_to_string(&mut output).map_err(|error| match error.kind() {
+        ErrorKind::TimedOut => SourceError::Timeout,
+        _ => SourceError::Network(error.to_string()),
+    })?;
+
+    let _ = channel.wait_close();
+    if let Ok(status) = channel.exit_status()
+        && status != 0 {
+        println!("ssh switch {}: {} exited with {}", address.ip(), command, status);
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+use crate::{DeviceLocation, Pollable, UpdateSender};
+use crate::device::{normalize_mac, Syslog, SyslogClient, SyslogRule, TrackedClient};
+use crate::error::SourceError;
+
+const MAX_MESSAGE_LENGTH: usize = 8192;
+/// The frame length of octet counting plus the space after it
+const MAX_FRAME_LENGTH_DIGITS: u64 = 6;
+
+struct SyslogHandler {
+    rules: Vec<SyslogRule>,
+    locations: HashMap<(Option<IpAddr>, String), String>,
+    clients: Arc<Mutex<HashMap<String, SyslogClient>>>,
+    updates: UpdateSender,
+}
+
+#[async_trait]
+impl Pollable for Syslog {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        if let Some(error) = self.bind_error.lock().unwrap().as_ref() {
+            return Err(SourceError::Io(error.to_string()));
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+
+        clients.retain(|_, client| client.tracked.updated.elapsed() < self.client_timeout);
+        Ok(clients.values().map(|client| client.tracked.client.clone()).collect())
+    }
+}
+
+impl Syslog {
+    pub fn new(udp: Option<&String>, tcp: Option<&String>, rules: Vec<SyslogRule>, locations: HashMap<(Option<IpAddr>, String), String>, client_timeout: Duration, updates: UpdateSender) -> Syslog {
+        let clients: Arc<Mutex<HashMap<String, SyslogClient>>> = Default::default();
+        let bind_error: Arc<Mutex<Option<String>>> = Default::default();
+
+        let handler = Arc::new(SyslogHandler {
+            rules,
+            locations,
+            clients: clients.clone(),
+            updates,
+        });
+
+        if let Some(udp) = udp {
+            tokio::spawn(listen_udp(udp.to_string(), handler.clone(), bind_error.clone()));
+        }
+        if let Some(tcp) = tcp {
+            tokio::spawn(listen_tcp(tcp.to_string(), handler, bind_error.clone()));
+        }
+
+        Syslog {
+            clients,
+            client_timeout,
+            bind_error,
+        }
+    }
+}
+
+impl SyslogHandler {
+    /// Several APs usually log the same interface names, so the location of the sender wins over a port-only one
+    fn get_port_location(&self, sender: IpAddr, port: &str) -> Option<&String> {
+        self.locations.get(&(Some(sender), port.to_string()))
+            .or_else(|| self.locations.get(&(None, port.to_string())))
+    }
+
+    /// Applies the first matching rule to the message, messages without a mac address are ignored
+    fn handle_message(&self, message: &str, sender: IpAddr) {
+        let Some((rule, captures)) = self.rules.iter()
+            .find_map(|rule| Some((rule, rule.regex.captures(message)?))) else {
+            return;
+        };
+
+        let Some(mac) = captures.name("mac").and_then(|mac| normalize_mac(mac.as_str())) else {
+            return;
+        };
+
+        let remove = rule.remove || captures.name("event")
+            .is_some_and(|event| rule.remove_events.iter().any(|remove_event| remove_event == event.as_str()));
+
+        let port = captures.name("port").map(|port| port.as_str());
+
+        let mut clients = self.clients.lock().unwrap();
+        if remove {
+            // a client that roamed on is still connected, the disconnect from where it was before is late
+            if clients.get(&mac).is_some_and(|client| client.reported_by(sender, port)) {
+                clients.remove(&mac);
+                self.updates.remove(&mac);
+            }
+        } else {
+            let entry = clients.entry(mac.to_string()).or_insert_with(|| SyslogClient {
+                sender,
+                port: None,
+                tracked: TrackedClient {
+                    client: DeviceLocation {
+                        device_mac: mac.to_string(),
+                        ..Default::default()
+                    },
+                    updated: Instant::now(),
+                },
+            });
+            entry.sender = sender;
+            entry.port = port.map(str::to_string);
+            entry.tracked.updated = Instant::now();
+
+            let client = &mut entry.tracked.client;
+            if let Some(ip) = captures.name("ip") {
+                client.ipv4 = ip.as_str().to_string();
+            }
+            if let Some(hostname) = captures.name("hostname") {
+                client.hostname = hostname.as_str().to_string();
+            }
+
+            let location = rule.location.as_ref()
+                .or(port.and_then(|port| self.get_port_location(sender, port)));
+
+            if let Some(location) = location {
+                client.location = location.to_string();
+                client.remote_ip = sender.to_string();
+                client.remote_mac = port.and_then(normalize_mac).unwrap_or_default();
+            }
+
+            self.updates.upsert(client.clone());
+        }
+    }
+}
+
+impl SyslogClient {
+    /// Messages without a port only have to come from the same host
+    fn reported_by(&self, sender: IpAddr, port: Option<&str>) -> bool {
+        self.sender == sender && (port.is_none() || self.port.is_none() || self.port.as_deref() == port)
+    }
+}
+
+async fn listen_udp(bind: String, handler: Arc<SyslogHandler>, bind_error: Arc<Mutex<Option<String>>>) {
+    let socket = match UdpSocket::bind(&bind).await {
+        Ok(socket) => socket,
+        Err(error) => {
+            let error = format!("failed to bind syslog udp listener to {}: {}", bind, error);
+            println!("{}", error);
+            *bind_error.lock().unwrap() = Some(error);
+            return;
+        }
+    };
+
+    let mut buffer = vec![0u8; MAX_MESSAGE_LENGTH];
+    loop {
+        match socket.recv_from(&mut buffer).await {
+            Ok((length, peer)) => handler.handle_message(&String::from_utf8_lossy(&buffer[..length]), peer.ip()),
+            Err(error) => println!("syslog udp listener: {}", error),
+        }
+    }
+}
+
+async fn listen_tcp(bind: String, handler: Arc<SyslogHandler>, bind_error: Arc<Mutex<Option<String>>>) {
+    let listener = match TcpListener::bind(&bind).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            let error = format!("failed to bind syslog tcp listener to {}: {}", bind, error);
+            println!("{}", error);
+            *bind_error.lock().unwrap() = Some(error);
+            return;
+        }
+    };
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                tokio::spawn(receive_tcp(stream, peer.ip(), handler.clone()));
+            }
+            Err(error) => println!("syslog tcp listener: {}", error),
+        }
+    }
+}
+
+/// Reads messages framed either by octet counting (RFC 6587 `<length> <message>`) or by newlines
+async fn receive_tcp(stream: TcpStream, peer: IpAddr, handler: Arc<SyslogHandler>) {
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let first_byte = match reader.fill_buf().await {
+            Ok([first_byte, ..]) => *first_byte,
+            _ => return,
+        };
+
+        let message = if first_byte.is_ascii_digit() {
+            let mut length = Vec::new();
+            if (&mut reader).take(MAX_FRAME_LENGTH_DIGITS).read_until(b' ', &mut length).await.is_err() {
+                return;
+            }
+
+            let length = String::from_utf8_lossy(&length).trim().parse::<usize>().unwrap_or_default();
+            if length == 0 || length > MAX_MESSAGE_LENGTH {
+                println!("syslog tcp listener: invalid frame from {}", peer);
+                return;
+            }
+
+            let mut message = vec![0u8; length];
+            if reader.read_exact(&mut message).await.is_err() {
+                return;
+            }
+            message
+        } else {
+            // a sender that never ends its line must not fill up the memory
+            let mut message = Vec::new();
+            match (&mut reader).take(MAX_MESSAGE_LENGTH as u64).read_until(b'\n', &mut message).await {
+                Ok(0) | Err(_) => return,
+                Ok(length) if length == MAX_MESSAGE_LENGTH && !message.ends_with(b"\n") => {
+                    println!("syslog tcp listener: message from {} exceeds {} bytes", peer, MAX_MESSAGE_LENGTH);
+                    return;
+                }
+                Ok(_) => message,
+            }
+        };
+
+        handler.handle_message(String::from_utf8_lossy(&message).trim_end(), peer);
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+/// A parser for TextFSM templates, supporting the value options `Filldown`, `Required` and `List`
+/// as well as the `Next`/`Continue`, `Record`/`NoRecord`/`Clear`/`Clearall` and `Error` actions
+pub struct TextFsm {
+    values: Vec<TextFsmValue>,
+    states: HashMap<String, Vec<TextFsmRule>>,
+}
+
+struct TextFsmValue {
+    name: String,
+    filldown: bool,
+    required: bool,
+    list: bool,
+}
+
+struct TextFsmRule {
+    regex: Regex,
+    line_action: LineAction,
+    record_action: RecordAction,
+    next_state: Option<String>,
+}
+
+#[derive(PartialEq)]
+enum LineAction {
+    Next,
+    Continue,
+    Error,
+}
+
+#[derive(PartialEq)]
+enum RecordAction {
+    NoRecord,
+    Record,
+    Clear,
+    Clearall,
+}
+
+impl TextFsm {
+    pub fn parse(template: &str) -> Result<TextFsm, String> {
+        let mut lines = template.lines().enumerate();
+        let mut values: Vec<TextFsmValue> = Vec::new();
+        let mut patterns: HashMap<String, String> = HashMap::new();
+
+        // the value definitions are followed by an empty line
+        for (number, line) in lines.by_ref() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.is_empty() {
+                break;
+            }
+
+            let Some(definition) = line.strip_prefix("Value ") else {
+                return Err(format!("line {}: expected value definition", number + 1));
+            };
+
+            let mut parts = definition.trim().splitn(3, ' ');
+            let (first, second, third) = (parts.next(), parts.next(), parts.next());
+            let (options, name, pattern) = match (first, second, third) {
+                (Some(options), Some(name), Some(pattern)) if !name.starts_with('(') => (options, name, pattern.trim()),
+                (Some(name), Some(_), _) => (
+                    "",
+                    name,
+                    definition.trim().strip_prefix(name).unwrap_or_default().trim(),
+                ),
+                _ => return Err(format!("line {}: invalid value definition", number + 1)),
+            };
+
+            if !pattern.starts_with('(') || !pattern.ends_with(')') {
+                return Err(format!("line {}: value pattern has to be enclosed in parentheses", number + 1));
+            }
+
+            let options: Vec<&str> = options.split(',').filter(|option| !option.is_empty()).collect();
+            patterns.insert(name.to_string(), format!("(?P<{}>{})", name, &pattern[1..pattern.len() - 1]));
+            values.push(TextFsmValue {
+                name: name.to_string(),
+                filldown: options.contains(&"Filldown"),
+                required: options.contains(&"Required"),
+                list: options.contains(&"List"),
+            });
+        }
+
+        let mut states: HashMap<String, Vec<TextFsmRule>> = HashMap::new();
+        let mut state: Option<String> = None;
+
+        for (number, line) in lines {
+            if line.trim().is_empty() || line.trim().starts_with('#') {
+                continue;
+            }
+
+            if !line.starts_with(char::is_whitespace) {
+                let name = line.trim().to_string();
+                states.insert(name.to_string(), Vec::new());
+                state = Some(name);
+                continue;
+            }
+
+            let Some(rules) = state.as_ref().and_then(|state| states.get_mut(state)) else {
+                return Err(format!("line {}: rule outside of a state", number + 1));
+            };
+
+            rules.push(
+                parse_rule(line.trim(), &patterns)
+                    .map_err(|error| format!("line {}: {}", number + 1, error))?
+            );
+        }
+
+        if !states.contains_key("Start") {
+            return Err("missing Start state".to_string());
+        }
+
+        for rules in states.values() {
+            for next_state in rules.iter().filter_map(|rule| rule.next_state.as_ref()) {
+                if next_state != "End" && next_state != "EOF" && !states.contains_key(next_state) {
+                    return Err(format!("unknown state {}", next_state));
+                }
+            }
+        }
+
+        Ok(TextFsm {
+            values,
+            states,
+        })
+    }
+
+    /// Parses `output` into rows, list values are joined with a comma
+    pub fn run(&self, output: &str) -> Result<Vec<HashMap<String, String>>, String> {
+        let mut rows: Vec<HashMap<String, String>> = Vec::new();
+        let mut current: Vec<Vec<String>> = vec![Vec::new(); self.values.len()];
+        let mut state = "Start";
+
+        'lines: for line in output.lines() {
+            for rule in self.states[state].iter() {
+                let Some(captures) = rule.regex.captures(line) else {
+                    continue;
+                };
+
+                for (index, value) in self.values.iter().enumerate() {
+                    if let Some(capture) = captures.name(&value.name) {
+                        if value.list {
+                            current[index].push(capture.as_str().to_string());
+                        } else {
+                            current[index] = vec![capture.as_str().to_string()];
+                        }
+                    }
+                }
+
+                if rule.line_action == LineAction::Error {
+                    return Err(format!("template error on line {}", line.trim()));
+                }
+
+                match rule.record_action {
+                    RecordAction::Record => self.record(&mut current, &mut rows),
+                    RecordAction::Clear => self.clear(&mut current, false),
+                    RecordAction::Clearall => self.clear(&mut current, true),
+                    RecordAction::NoRecord => {}
+                }
+
+                if rule.line_action == LineAction::Continue {
+                    continue;
+                }
+
+                match rule.next_state.as_deref() {
+                    Some("End") => return Ok(rows),
+                    Some("EOF") => break 'lines,
+                    Some(next_state) => state = next_state,
+                    None => {}
+                }
+
+                continue 'lines;
+            }
+        }
+
+        // an explicitly defined EOF state suppresses the implicit record at the end
+        if !self.states.contains_key("EOF") {
+            self.record(&mut current, &mut rows);
+        }
+        Ok(rows)
+    }
+
+    fn record(&self, current: &mut [Vec<String>], rows: &mut Vec<HashMap<String, String>>) {
+        let complete = self.values.iter()
+            .zip(current.iter())
+            .all(|(value, captured)| !value.required || !captured.is_empty());
+        let empty = self.values.iter()
+            .zip(current.iter())
+            .all(|(value, captured)| value.filldown || captured.is_empty());
+
+        if complete && !empty {
+            rows.push(
+                self.values.iter()
+                    .zip(current.iter())
+                    .map(|(value, captured)| (value.name.to_string(), captured.join(",")))
+                    .collect()
+            );
+        }
+
+        self.clear(current, false);
+    }
+
+    fn clear(&self, current: &mut [Vec<String>], all: bool) {
+        for (value, captured) in self.values.iter().zip(current.iter_mut()) {
+            if all || !value.filldown {
+                captured.clear();
+            }
+        }
+    }
+}
+
+fn parse_rule(line: &str, patterns: &HashMap<String, String>) -> Result<TextFsmRule, String> {
+    let (pattern, action) = match line.rsplit_once(" -> ") {
+        Some((pattern, action)) => (pattern.trim(), action.trim()),
+        None => (line, ""),
+    };
+
+    if !pattern.starts_with('^') {
+        return Err("rules have to start with ^".to_string());
+    }
+
+    let mut regex = String::new();
+    let mut rest = pattern;
+    while let Some(position) = rest.find('$') {
+        regex.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        if let Some(stripped) = rest.strip_prefix('$') {
+            regex.push('$');
+            rest = stripped;
+            continue;
+        }
+
+        let (name, remaining) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.split_once('}') {
+                Some((name, remaining)) => (name, remaining),
+                None => return Err("unterminated value reference".to_string()),
+            },
+            None => {
+                let end = rest.find(|char: char| !char.is_alphanumeric() && char != '_').unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+
+        match patterns.get(name) {
+            Some(value_pattern) => regex.push_str(value_pattern),
+            None if name.is_empty() => regex.push('$'),
+            None => return Err(format!("unknown value {}", name)),
+        }
+        rest = remaining;
+    }
+    regex.push_str(rest);
+
+    let mut rule = TextFsmRule {
+        regex: Regex::new(&regex).map_err(|error| error.to_string())?,
+        line_action: LineAction::Next,
+        record_action: RecordAction::NoRecord,
+        next_state: None,
+    };
+
+    let mut words = action.split_whitespace();
+    if let Some(actions) = words.next() {
+        let mut has_action = false;
+        for action in actions.split('.') {
+            match action {
+                "Next" => rule.line_action = LineAction::Next,
+                "Continue" => rule.line_action = LineAction::Continue,
+                "Error" => rule.line_action = LineAction::Error,
+                "NoRecord" => rule.record_action = RecordAction::NoRecord,
+                "Record" => rule.record_action = RecordAction::Record,
+                "Clear" => rule.record_action = RecordAction::Clear,
+                "Clearall" => rule.record_action = RecordAction::Clearall,
+                _ => continue,
+            }
+            has_action = true;
+        }
+
+        // a single word which is no action is the next state, an error may be followed by a message
+        if rule.line_action != LineAction::Error {
+            rule.next_state = if has_action { words.next() } else { Some(actions) }.map(String::from);
+        }
+    }
+
+    if rule.line_action == LineAction::Continue && rule.next_state.is_some() {
+        return Err("Continue can not change the state".to_string());
+    }
+
+    Ok(rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CISCO_TEMPLATE: &str = r"Value VLAN (\d+)
+Value MAC ([0-9a-fA-F]{4}\.[0-9a-fA-F]{4}\.[0-9a-fA-F]{4})
+Value PORT (\S+)
+
+Start
+  ^\s*\*?\s*${VLAN}\s+${MAC}\s+\S+\s+(?:\S+\s+){0,3}${PORT}\s*$$ -> Record
+";
+
+    const CISCO_OUTPUT: &str = "          Mac Address Table
+-------------------------------------------
+
+Vlan    Mac Address       Type        Ports
+----    -----------       --------    -----
+  23    0011.2233.4455    DYNAMIC     Gi1/0/5
+  23    0011.2233.4466    STATIC      Gi1/0/6
+ All    0100.0ccc.cccc    STATIC      CPU
+Total Mac Addresses for this criterion: 3
+";
+
+    fn parse(template: &str) -> TextFsm {
+        TextFsm::parse(template).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    fn column<'a>(row: &'a HashMap<String, String>, name: &str) -> &'a str {
+        row.get(name).map(String::as_str).unwrap_or_default()
+    }
+
+    #[test]
+    fn parses_value_options() {
+        let template = parse("Value Filldown,Required INTERFACE (\\S+)\nValue List VLANS ((?:\\d+))\nValue MAC (\\S+)\n\nStart\n  ^${MAC}\n");
+
+        assert_eq!(template.values.len(), 3);
+        assert!(template.values[0].filldown && template.values[0].required && !template.values[0].list);
+        assert!(template.values[1].list && !template.values[1].filldown);
+        assert!(!template.values[2].filldown && !template.values[2].required && !template.values[2].list);
+        assert_eq!(template.values[1].name, "VLANS");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(TextFsm::parse("MAC (\\S+)\n\nStart\n  ^${MAC}\n").is_err());
+        assert!(TextFsm::parse("Value MAC \\S+\n\nStart\n  ^${MAC}\n").is_err());
+        assert!(TextFsm::parse("Value MAC\n\nStart\n  ^x\n").is_err());
+    }
+
+    #[test]
+    fn parses_rule_actions() {
+        let template = parse("Value MAC (\\S+)\n\nStart\n  ^${MAC} -> Continue.Record\n  ^next -> Details\n  ^error -> Error bad line\n\nDetails\n  ^done -> Clearall Start\n");
+
+        let start = &template.states["Start"];
+        assert!(start[0].line_action == LineAction::Continue && start[0].record_action == RecordAction::Record);
+        assert_eq!(start[1].next_state.as_deref(), Some("Details"));
+        assert!(start[2].line_action == LineAction::Error && start[2].next_state.is_none());
+
+        let details = &template.states["Details"];
+        assert!(details[0].record_action == RecordAction::Clearall);
+        assert_eq!(details[0].next_state.as_deref(), Some("Start"));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\nStart\n  ${MAC}\n").is_err());
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\nStart\n  ^${PORT}\n").is_err());
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\nStart\n  ^${MAC\n").is_err());
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\nStart\n  ^${MAC} -> Missing\n").is_err());
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\nStart\n  ^${MAC} -> Continue Start\n").is_err());
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\nOther\n  ^${MAC}\n").is_err());
+        assert!(TextFsm::parse("Value MAC (\\S+)\n\n  ^${MAC}\n").is_err());
+    }
+
+    #[test]
+    fn parses_cisco_mac_table() {
+        let rows = parse(CISCO_TEMPLATE).run(CISCO_OUTPUT).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(column(&rows[0], "VLAN"), "23");
+        assert_eq!(column(&rows[0], "MAC"), "0011.2233.4455");
+        assert_eq!(column(&rows[0], "PORT"), "Gi1/0/5");
+        assert_eq!(column(&rows[1], "PORT"), "Gi1/0/6");
+    }
+
+    #[test]
+    fn skips_truncated_lines() {
+        let truncated = &CISCO_OUTPUT[..CISCO_OUTPUT.find("0011.2233.4466").unwrap() + 9];
+        let rows = parse(CISCO_TEMPLATE).run(truncated).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(column(&rows[0], "MAC"), "0011.2233.4455");
+        assert!(parse(CISCO_TEMPLATE).run("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn fills_down_and_requires_values() {
+        let template = parse("Value Filldown PORT (\\S+)\nValue Required MAC (\\S+)\n\nStart\n  ^Port ${PORT}\n  ^\\s+${MAC} -> Record\n");
+        let rows = template.run("Port 1\n  aa\n  bb\nPort 2\nPort 3\n  cc\n").unwrap();
+
+        let rows: Vec<(&str, &str)> = rows.iter().map(|row| (column(row, "PORT"), column(row, "MAC"))).collect();
+        assert_eq!(rows, vec![("1", "aa"), ("1", "bb"), ("3", "cc")]);
+    }
+
+    #[test]
+    fn joins_list_values() {
+        let template = parse("Value PORT (\\S+)\nValue List MACS (\\S+)\n\nStart\n  ^Port -> Continue.Record\n  ^Port ${PORT}\n  ^\\s+${MACS}\n");
+        let rows = template.run("Port 1\n  aa\n  bb\nPort 2\n  cc\n").unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(column(&rows[0], "MACS"), "aa,bb");
+        assert_eq!(column(&rows[1], "PORT"), "2");
+        assert_eq!(column(&rows[1], "MACS"), "cc");
+    }
+
+    #[test]
+    fn stops_on_error_and_end() {
+        let template = parse("Value MAC (\\S+)\n\nStart\n  ^% -> Error\n  ^${MAC} -> Record\n");
+        assert!(template.run("aa\n% Invalid input\n").is_err());
+
+        let template = parse("Value MAC (\\S+)\n\nStart\n  ^--- -> End\n  ^${MAC} -> Record\n");
+        let rows = template.run("aa\n---\nbb\n").unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+}
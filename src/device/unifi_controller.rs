@@ -1,16 +1,23 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
 
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
+use reqwest::header::{HeaderMap, HeaderValue};
+use rumqttc::{AsyncClient, Publish, QoS};
 use serde::{Deserialize, Serialize};
-use serde::de::Unexpected::Str;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time;
 
 use crate::{DeviceLocation, Pollable};
 use crate::device::{UniFiAP, UniFiController};
+use crate::error::SourceError;
 
 #[derive(Deserialize)]
 struct UniFiMetaResponseData {
     rc: String,
+    msg: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -38,75 +45,121 @@ struct UniFiLoginData {
     strict: bool,
 }
 
-#[async_trait]
-impl Pollable for UniFiController {
-    async fn poll_device(&self) -> Vec<DeviceLocation> {
-        let body = self.client
-            .get(&self.data_url)
-            .send()
-            .await;
+#[derive(Deserialize)]
+struct UniFiCommand {
+    cmd: String,
+    mac: String,
+}
+
+#[derive(Serialize)]
+struct UniFiCommandReply {
+    cmd: String,
+    mac: String,
+    success: bool,
+    message: String,
+}
 
-        if body.is_err() {
-            println!("{}", body.unwrap_err().to_string());
-            return Vec::new();
+const UNIFI_STAMGR_COMMANDS: [&str; 4] = ["kick-sta", "block-sta", "unblock-sta", "forget-sta"];
+
+/// Error messages of the controller caused by the credentials or their permissions
+const UNIFI_AUTH_ERRORS: [&str; 4] = ["api.err.Invalid", "api.err.NoPermission", "api.err.Ubic2faTokenRequired", "api.err.LoginRequired"];
+
+const UNIFI_LOGIN_ATTEMPTS: u32 = 3;
+const UNIFI_LOGIN_BACKOFF: Duration = Duration::from_millis(500);
+
+enum UniFiError {
+    Network(String),
+    SessionExpired,
+    Auth(String),
+    Api(String),
+    Parse(String),
+}
+
+impl Display for UniFiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UniFiError::Network(error) => write!(f, "network error: {}", error),
+            UniFiError::SessionExpired => write!(f, "session expired"),
+            UniFiError::Auth(error) => write!(f, "authentication failed: {}", error),
+            UniFiError::Api(error) => write!(f, "api error: {}", error),
+            UniFiError::Parse(error) => write!(f, "invalid response: {}", error),
         }
-        let body = body.unwrap().text().await;
-        if body.is_err() {
-            println!("{}", body.unwrap_err().to_string());
-            return Vec::new();
+    }
+}
+
+impl From<UniFiError> for SourceError {
+    fn from(error: UniFiError) -> SourceError {
+        match error {
+            UniFiError::Network(error) => SourceError::Network(error),
+            UniFiError::SessionExpired => SourceError::Auth("session expired".to_string()),
+            UniFiError::Auth(error) => SourceError::Auth(error),
+            UniFiError::Api(error) if UNIFI_AUTH_ERRORS.contains(&error.as_str()) => SourceError::Auth(error),
+            // the controller was reached and answered, but not with the data that was asked for
+            UniFiError::Api(error) => SourceError::Parse(format!("api error: {}", error)),
+            UniFiError::Parse(error) => SourceError::Parse(error),
         }
-        let body = body.unwrap();
-
-        let response: UniFiMetaResponse = serde_json::from_str(&body).unwrap();
-        return if response.meta.rc == "error" {
-            println!("failed to request devices from unifi controller...trying to log in", );
-
-            let is_logged_in = self.login().await;
-            if is_logged_in {
-                self.poll_device().await
-            } else {
-                println!("failed to login to unifi controller...");
-                Vec::new()
+    }
+}
+
+#[async_trait]
+impl Pollable for UniFiController {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let body = self.authenticated_request(&self.data_url, None).await?;
+        let devices: UniFiDeviceResponse = serde_json::from_str(&body)?;
+
+        Ok(devices.data.iter().map(|device| {
+            let location = self.get_ap_location(device);
+
+            DeviceLocation {
+                ipv4: device.ip.clone().unwrap_or_default(),
+                device_mac: device.mac.to_string(),
+                remote_mac: device.ap_mac.to_string(),
+                location,
+                ..Default::default()
             }
-        } else {
-            let mut devices: UniFiDeviceResponse = serde_json::from_str(&body).unwrap();
-
-            devices.data.iter_mut().map(|device| {
-                let location = self.get_ap_location(&device);
-
-                DeviceLocation {
-                    ipv4: device.ip.clone().unwrap_or(String::new()),
-                    ipv6: Vec::new(),
-                    device_mac: device.mac.to_string(),
-                    remote_mac: device.ap_mac.to_string(),
-                    remote_ip: "".to_string(),
-                    location,
-                }
-            }).collect()
-        };
+        }).collect())
     }
 }
 
 impl UniFiController {
-    pub fn new(ip: &str, user: &String, password: &String, access_points: HashMap<String, UniFiAP>) -> UniFiController {
-        let client = Client::builder()
-            .cookie_store(true)
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-
-        UniFiController {
+    pub fn new(ip: &str, user: &String, password: &String, api_key: Option<&String>, access_points: HashMap<String, UniFiAP>) -> Result<UniFiController, String> {
+        let mut builder = Client::builder()
+            .danger_accept_invalid_certs(true);
+
+        // API keys are only supported by UniFi OS, which serves the network application behind a proxy path
+        let base_url = if let Some(api_key) = api_key {
+            let mut headers = HeaderMap::new();
+            let mut api_key = HeaderValue::from_str(api_key)
+                .map_err(|_| "api_key contains characters not allowed in a http header".to_string())?;
+            api_key.set_sensitive(true);
+            headers.insert("X-API-KEY", api_key);
+            builder = builder.default_headers(headers);
+
+            format!("https://{}/proxy/network", ip)
+        } else {
+            builder = builder.cookie_store(true);
+
+            format!("https://{}:8443", ip)
+        };
+
+        Ok(UniFiController {
             ip: ip.to_string(),
-            data_url: format!("https://{}:8443/api/s/default/stat/sta", ip.to_string()),
-            login_url: format!("https://{}:8443/api/login", ip.to_string()),
+            data_url: format!("{}/api/s/default/stat/sta", base_url),
+            command_url: format!("{}/api/s/default/cmd/stamgr", base_url),
+            login_url: format!("{}/api/login", base_url),
             user: user.to_string(),
             password: password.to_string(),
-            client,
+            uses_api_key: api_key.is_some(),
+            client: builder.build().unwrap(),
             access_points,
-        }
+        })
     }
 
-    async fn login(&self) -> bool {
+    async fn login(&self) -> Result<(), UniFiError> {
+        if self.uses_api_key {
+            return Err(UniFiError::Auth("api key rejected".to_string()));
+        }
+
         let login = UniFiLoginData {
             username: self.user.to_string(),
             password: self.password.to_string(),
@@ -114,27 +167,132 @@ impl UniFiController {
             strict: true,
         };
 
-        let body = self.client
-            .post(&self.login_url)
-            .body(serde_json::to_string(&login).unwrap())
+        match self.send_request(&self.login_url, Some(serde_json::to_string(&login).unwrap())).await {
+            Ok(_) => Ok(()),
+            Err(UniFiError::SessionExpired) => Err(UniFiError::Auth("invalid credentials".to_string())),
+            Err(UniFiError::Api(error)) => Err(UniFiError::Auth(error)),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Sends a request and transparently re-authenticates when the session has expired.
+    /// Logins are attempted a limited number of times with an increasing delay in between.
+    async fn authenticated_request(&self, url: &str, body: Option<String>) -> Result<String, UniFiError> {
+        let mut attempt = 0;
+
+        loop {
+            match self.send_request(url, body.clone()).await {
+                Err(UniFiError::SessionExpired) if attempt < UNIFI_LOGIN_ATTEMPTS => {}
+                Err(UniFiError::SessionExpired) => return Err(UniFiError::Auth("session expired after login".to_string())),
+                result => return result,
+            }
+
+            if attempt > 0 {
+                time::sleep(UNIFI_LOGIN_BACKOFF * 2u32.pow(attempt - 1)).await;
+            }
+            attempt += 1;
+
+            println!("unifi controller {}: session expired...trying to log in ({}/{})", self.ip, attempt, UNIFI_LOGIN_ATTEMPTS);
+            match self.login().await {
+                Ok(()) => {}
+                Err(UniFiError::Network(error)) if attempt < UNIFI_LOGIN_ATTEMPTS => {
+                    println!("unifi controller {}: login failed: {}", self.ip, error);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Sends a GET request, or a POST request if a body is given, and checks the response meta data
+    async fn send_request(&self, url: &str, body: Option<String>) -> Result<String, UniFiError> {
+        let request = match body {
+            Some(body) => self.client.post(url).body(body),
+            None => self.client.get(url),
+        };
+
+        let response = request
             .send()
             .await
-            .unwrap()
+            .map_err(|error| UniFiError::Network(error.to_string()))?;
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(UniFiError::SessionExpired);
+        }
+
+        let body = response
             .text()
             .await
-            .unwrap();
+            .map_err(|error| UniFiError::Network(error.to_string()))?;
 
-        let response: UniFiMetaResponse = serde_json::from_str(&body).unwrap();
-        return response.meta.rc != "error";
+        let response: UniFiMetaResponse = serde_json::from_str(&body)
+            .map_err(|_| UniFiError::Parse(format!("unexpected response with status {}", status)))?;
+
+        if response.meta.rc == "ok" {
+            Ok(body)
+        } else {
+            match response.meta.msg {
+                Some(msg) if msg == "api.err.LoginRequired" => Err(UniFiError::SessionExpired),
+                Some(msg) => Err(UniFiError::Api(msg)),
+                None => Err(UniFiError::Api(response.meta.rc)),
+            }
+        }
     }
 
-    fn get_ap_location(&self, device: &UniFiDeviceData) -> String {
-        let ap = self.access_points.get(&device.ap_mac);
+    /// Executes client commands received over mqtt and publishes the result to `reply_topic`
+    pub async fn handle_commands(self, mut commands: UnboundedReceiver<Publish>, mqtt_client: AsyncClient, reply_topic: String) {
+        while let Some(publish) = commands.recv().await {
+            let reply = match serde_json::from_slice::<UniFiCommand>(&publish.payload) {
+                Ok(command) => {
+                    let result = self.send_command(&command.cmd, &command.mac).await;
+                    UniFiCommandReply {
+                        cmd: command.cmd,
+                        mac: command.mac,
+                        success: result.is_ok(),
+                        message: result.unwrap_or_else(|error| error),
+                    }
+                }
+                Err(error) => UniFiCommandReply {
+                    cmd: String::new(),
+                    mac: String::new(),
+                    success: false,
+                    message: format!("invalid command: {}", error),
+                }
+            };
+
+            println!("unifi controller {}: {} {} -> {}", self.ip, reply.cmd, reply.mac, reply.message);
+
+            let _ = mqtt_client.publish(
+                &reply_topic,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_string(&reply).unwrap(),
+            ).await;
+        }
+    }
 
-        return if ap.is_some() {
-            ap.unwrap().location.to_string()
+    async fn send_command(&self, cmd: &str, mac: &str) -> Result<String, String> {
+        if !UNIFI_STAMGR_COMMANDS.contains(&cmd) {
+            return Err(format!("unknown command {}", cmd));
+        }
+
+        let mac = mac.to_lowercase();
+        let body = if cmd == "forget-sta" {
+            serde_json::json!({ "cmd": cmd, "macs": [mac] })
         } else {
-            String::new()
+            serde_json::json!({ "cmd": cmd, "mac": mac })
         };
+
+        match self.authenticated_request(&self.command_url, Some(body.to_string())).await {
+            Ok(_) => Ok("ok".to_string()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    fn get_ap_location(&self, device: &UniFiDeviceData) -> String {
+        self.access_points
+            .get(&device.ap_mac)
+            .map(|ap| ap.location.to_string())
+            .unwrap_or_default()
     }
 }
\ No newline at end of file
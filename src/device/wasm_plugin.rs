@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use reqwest::{Client, Method, Url};
+use reqwest::redirect::Policy;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::task;
+use tokio::time;
+use wasmi::{Caller, Config, Engine, Error, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, Val};
+use wasmi::core::TrapCode;
+
+use crate::{DeviceLocation, Pollable};
+use crate::device::WasmPlugin;
+use crate::error::SourceError;
+
+const HOST_MODULE: &str = "location2mqtt";
+const WASI_MODULE: &str = "wasi_snapshot_preview1";
+
+/// The wasi functions implemented by `define_wasi`
+const WASI_FUNCTIONS: [&str; 8] = [
+    "fd_write", "args_sizes_get", "environ_sizes_get", "args_get", "environ_get", "clock_time_get", "random_get", "proc_exit",
+];
+
+const WASI_SUCCESS: i32 = 0;
+const WASI_EBADF: i32 = 8;
+const WASI_EFAULT: i32 = 21;
+const WASI_ENOSYS: i32 = 52;
+
+/// State of a single plugin invocation, a fresh store is created for every poll
+pub struct PluginState {
+    name: String,
+    config: String,
+    allowed_hosts: Vec<String>,
+    client: Client,
+    runtime: Handle,
+    deadline: Instant,
+    limits: StoreLimits,
+}
+
+#[derive(Deserialize)]
+struct PluginHttpRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Serialize)]
+struct PluginHttpResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[async_trait]
+impl Pollable for WasmPlugin {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError> {
+        let state = PluginState {
+            name: self.name.to_string(),
+            config: self.config.to_string(),
+            allowed_hosts: self.allowed_hosts.clone(),
+            client: self.client.clone(),
+            runtime: Handle::current(),
+            deadline: Instant::now() + self.timeout,
+            limits: StoreLimitsBuilder::new().memory_size(self.memory_limit).build(),
+        };
+
+        let engine = self.engine.clone();
+        let module = self.module.clone();
+        let linker = self.linker.clone();
+        let fuel = self.fuel;
+
+        // the interpreter blocks, fuel bounds the cpu time even if the timeout below gives up waiting
+        let result = time::timeout(
+            self.timeout,
+            task::spawn_blocking(move || run_plugin(&engine, &module, &linker, state, fuel)),
+        ).await;
+
+        let output = match result {
+            Ok(Ok(Ok(output))) => output,
+            Ok(Ok(Err(error))) => return Err(SourceError::Io(error)),
+            Ok(Err(error)) => return Err(SourceError::Io(error.to_string())),
+            Err(_) => return Err(SourceError::Timeout),
+        };
+
+        let clients: Vec<DeviceLocation> = serde_json::from_slice(&output)?;
+        Ok(clients.into_iter().filter(|client| !client.device_mac.is_empty()).collect())
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+}
+
+impl WasmPlugin {
+    pub fn new(
+        path: &str,
+        config: String,
+        allowed_hosts: Vec<String>,
+        memory_limit: usize,
+        fuel: u64,
+        timeout: Duration,
+    ) -> Result<WasmPlugin, String> {
+        let wasm = std::fs::read(path).map_err(|error| error.to_string())?;
+
+        let mut engine_config = Config::default();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config);
+
+        let module = Module::new(&engine, &wasm[..]).map_err(|error| error.to_string())?;
+        let linker = build_linker(&engine, &module).map_err(|error| error.to_string())?;
+
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .unwrap();
+
+        Ok(WasmPlugin {
+            name: path.to_string(),
+            engine,
+            module: Arc::new(module),
+            linker: Arc::new(linker),
+            config,
+            allowed_hosts: allowed_hosts.iter().map(|host| get_origin(host)).collect::<Result<_, _>>()?,
+            client,
+            memory_limit,
+            fuel,
+            timeout,
+        })
+    }
+}
+
+/// Instantiates the plugin and calls its `poll` export, which returns a json array of clients
+fn run_plugin(engine: &Engine, module: &Module, linker: &Linker<PluginState>, state: PluginState, fuel: u64) -> Result<Vec<u8>, String> {
+    let mut store = Store::new(engine, state);
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(fuel).map_err(|error| error.to_string())?;
+
+    let instance = linker
+        .instantiate(&mut store, module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(describe_error)?;
+
+    // reactor modules built for wasi expect their runtime to be initialized first
+    if let Ok(initialize) = instance.get_typed_func::<(), ()>(&store, "_initialize") {
+        initialize.call(&mut store, ()).map_err(describe_error)?;
+    }
+
+    let poll = instance
+        .get_typed_func::<(), i64>(&store, "poll")
+        .map_err(|_| "missing export poll".to_string())?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or_else(|| "missing export memory".to_string())?;
+
+    let (ptr, len) = unpack(poll.call(&mut store, ()).map_err(describe_error)?);
+    read_memory(&memory, &store, ptr, len).ok_or_else(|| "poll result out of bounds".to_string())
+}
+
+fn describe_error(error: Error) -> String {
+    match (error.as_trap_code(), error.i32_exit_status()) {
+        (Some(TrapCode::OutOfFuel), _) => "ran out of fuel".to_string(),
+        (_, Some(status)) => format!("exited with status {}", status),
+        _ => error.to_string(),
+    }
+}
+
+/// Guest buffers are passed as a single i64 with the pointer in the upper and the length in the lower half
+fn pack(ptr: u32, len: u32) -> i64 {
+    (((ptr as u64) << 32) | len as u64) as i64
+}
+
+fn unpack(value: i64) -> (u32, u32) {
+    ((value as u64 >> 32) as u32, value as u32)
+}
+
+fn read_memory(memory: &Memory, store: &Store<PluginState>, ptr: u32, len: u32) -> Option<Vec<u8>> {
+    let data = memory.data(store);
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize)?;
+    data.get(start..end).map(|bytes| bytes.to_vec())
+}
+
+fn caller_memory(caller: &Caller<'_, PluginState>) -> Option<Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+fn read_guest(caller: &Caller<'_, PluginState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = caller_memory(caller)?;
+    let start = ptr as u32 as usize;
+    let end = start.checked_add(len as u32 as usize)?;
+    memory.data(caller).get(start..end).map(|bytes| bytes.to_vec())
+}
+
+fn write_guest(caller: &mut Caller<'_, PluginState>, ptr: i32, bytes: &[u8]) -> bool {
+    match caller_memory(caller) {
+        Some(memory) => memory.write(caller, ptr as u32 as usize, bytes).is_ok(),
+        None => false,
+    }
+}
+
+/// Copies `bytes` into a buffer allocated through the guest's `alloc` export
+fn return_to_guest(caller: &mut Caller<'_, PluginState>, bytes: &[u8]) -> Result<i64, Error> {
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| Error::new("missing export alloc"))?
+        .typed::<i32, i32>(&*caller)?;
+
+    let len = i32::try_from(bytes.len()).map_err(|_| Error::new("buffer too large"))?;
+    let ptr = alloc.call(&mut *caller, len)?;
+
+    if !write_guest(caller, ptr, bytes) {
+        return Err(Error::new("alloc returned an invalid buffer"));
+    }
+
+    Ok(pack(ptr as u32, len as u32))
+}
+
+fn check_deadline(caller: &Caller<'_, PluginState>) -> Result<(), Error> {
+    if Instant::now() >= caller.data().deadline {
+        return Err(Error::new("time limit exceeded"));
+    }
+
+    Ok(())
+}
+
+/// Plugins export `memory`, `alloc(len) -> ptr` and `poll() -> buffer` and may import
+/// `log(ptr, len)`, `get_config() -> buffer` and `http_fetch(ptr, len) -> buffer` from the host module
+fn build_linker(engine: &Engine, module: &Module) -> Result<Linker<PluginState>, Error> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap(HOST_MODULE, "log", |caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+        if let Some(message) = read_guest(&caller, ptr, len) {
+            println!("wasm plugin {}: {}", caller.data().name, String::from_utf8_lossy(&message));
+        }
+    })?;
+
+    linker.func_wrap(HOST_MODULE, "get_config", |mut caller: Caller<'_, PluginState>| -> Result<i64, Error> {
+        let config = caller.data().config.clone();
+        return_to_guest(&mut caller, config.as_bytes())
+    })?;
+
+    linker.func_wrap(HOST_MODULE, "http_fetch", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| -> Result<i64, Error> {
+        check_deadline(&caller)?;
+
+        let response = match read_guest(&caller, ptr, len) {
+            Some(request) => http_fetch(caller.data(), &request),
+            None => fetch_error("request out of bounds".to_string()),
+        };
+
+        check_deadline(&caller)?;
+        return_to_guest(&mut caller, serde_json::to_string(&response).unwrap().as_bytes())
+    })?;
+
+    define_wasi(&mut linker)?;
+
+    // everything else of wasi (files, sockets, ...) is unavailable to plugins
+    for import in module.imports() {
+        if import.module() != WASI_MODULE || WASI_FUNCTIONS.contains(&import.name()) {
+            continue;
+        }
+
+        if let Some(func_type) = import.ty().func() {
+            let func_type = func_type.clone();
+            let results: Vec<Val> = func_type.results().iter().map(|ty| Val::default(*ty)).collect();
+
+            linker.func_new(WASI_MODULE, import.name(), func_type, move |_caller, _params, outputs| {
+                for (output, result) in outputs.iter_mut().zip(results.iter()) {
+                    *output = match result {
+                        Val::I32(_) => Val::I32(WASI_ENOSYS),
+                        result => result.clone(),
+                    };
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(linker)
+}
+
+/// The subset of wasi needed by common toolchains, stdout and stderr end up in the log
+fn define_wasi(linker: &mut Linker<PluginState>) -> Result<(), Error> {
+    linker.func_wrap(WASI_MODULE, "fd_write", |mut caller: Caller<'_, PluginState>, fd: i32, iovs: i32, iovs_len: i32, written: i32| -> i32 {
+        if fd != 1 && fd != 2 {
+            return WASI_EBADF;
+        }
+
+        let mut output = Vec::new();
+        for index in 0..iovs_len {
+            let Some(iov) = read_guest(&caller, iovs.wrapping_add(index.wrapping_mul(8)), 8) else {
+                return WASI_EFAULT;
+            };
+            let buf = i32::from_le_bytes([iov[0], iov[1], iov[2], iov[3]]);
+            let buf_len = i32::from_le_bytes([iov[4], iov[5], iov[6], iov[7]]);
+
+            match read_guest(&caller, buf, buf_len) {
+                Some(bytes) => output.extend_from_slice(&bytes),
+                None => return WASI_EFAULT,
+            }
+        }
+
+        for line in String::from_utf8_lossy(&output).lines().filter(|line| !line.trim().is_empty()) {
+            println!("wasm plugin {}: {}", caller.data().name, line);
+        }
+
+        if write_guest(&mut caller, written, &(output.len() as u32).to_le_bytes()) {
+            WASI_SUCCESS
+        } else {
+            WASI_EFAULT
+        }
+    })?;
+
+    // plugins get neither arguments nor environment variables
+    for name in ["args_sizes_get", "environ_sizes_get"] {
+        linker.func_wrap(WASI_MODULE, name, |mut caller: Caller<'_, PluginState>, count: i32, size: i32| -> i32 {
+            if write_guest(&mut caller, count, &[0; 4]) && write_guest(&mut caller, size, &[0; 4]) {
+                WASI_SUCCESS
+            } else {
+                WASI_EFAULT
+            }
+        })?;
+    }
+
+    for name in ["args_get", "environ_get"] {
+        linker.func_wrap(WASI_MODULE, name, |_: i32, _: i32| -> i32 { WASI_SUCCESS })?;
+    }
+
+    linker.func_wrap(WASI_MODULE, "clock_time_get", |mut caller: Caller<'_, PluginState>, _clock: i32, _precision: i64, time: i32| -> i32 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+
+        if write_guest(&mut caller, time, &now.to_le_bytes()) {
+            WASI_SUCCESS
+        } else {
+            WASI_EFAULT
+        }
+    })?;
+
+    linker.func_wrap(WASI_MODULE, "random_get", |mut caller: Caller<'_, PluginState>, buf: i32, len: i32| -> i32 {
+        let mut bytes = Vec::with_capacity(len.max(0) as usize);
+        while bytes.len() < len.max(0) as usize {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(bytes.len());
+            bytes.extend_from_slice(&hasher.finish().to_le_bytes());
+        }
+        bytes.truncate(len.max(0) as usize);
+
+        if write_guest(&mut caller, buf, &bytes) {
+            WASI_SUCCESS
+        } else {
+            WASI_EFAULT
+        }
+    })?;
+
+    linker.func_wrap(WASI_MODULE, "proc_exit", |status: i32| -> Result<(), Error> {
+        Err(Error::i32_exit(status))
+    })?;
+
+    Ok(())
+}
+
+/// Performs a request for the plugin, only hosts from the plugin configuration may be contacted
+fn http_fetch(state: &PluginState, request: &[u8]) -> PluginHttpResponse {
+    let request: PluginHttpRequest = match serde_json::from_slice(request) {
+        Ok(request) => request,
+        Err(error) => return fetch_error(format!("invalid request: {}", error)),
+    };
+
+    let url = match Url::parse(&request.url) {
+        Ok(url) => url,
+        Err(error) => return fetch_error(format!("invalid url: {}", error)),
+    };
+
+    let origin = url.origin().ascii_serialization();
+    if !state.allowed_hosts.contains(&origin) {
+        return fetch_error(format!("{} is not allowed", origin));
+    }
+
+    let method = match Method::from_bytes(request.method.to_uppercase().as_bytes()) {
+        Ok(method) => method,
+        Err(_) => return fetch_error(format!("invalid method {}", request.method)),
+    };
+
+    let mut builder = state.client
+        .request(method, url)
+        .timeout(state.deadline.saturating_duration_since(Instant::now()));
+    for (name, value) in request.headers.iter() {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = request.body {
+        builder = builder.body(body);
+    }
+
+    let result = state.runtime.block_on(async {
+        let response = builder.send().await?;
+        let status = response.status().as_u16();
+        Ok::<_, reqwest::Error>((status, response.text().await?))
+    });
+
+    match result {
+        Ok((status, body)) => PluginHttpResponse {
+            status: Some(status),
+            body: Some(body),
+            error: None,
+        },
+        Err(error) => fetch_error(error.to_string()),
+    }
+}
+
+/// Allowed hosts are compared by scheme, host and port, a plain host name stands for `https://<host>`
+fn get_origin(host: &str) -> Result<String, String> {
+    let url = if host.contains("://") { host.to_string() } else { format!("https://{}", host) };
+
+    match Url::parse(&url) {
+        Ok(url) if url.has_host() => Ok(url.origin().ascii_serialization()),
+        _ => Err(format!("invalid allowed host {}", host)),
+    }
+}
+
+fn fetch_error(error: String) -> PluginHttpResponse {
+    PluginHttpResponse {
+        status: None,
+        body: None,
+        error: Some(error),
+    }
+}
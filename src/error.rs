@@ -0,0 +1,80 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+/// The reason a source could not deliver its clients
+#[derive(Clone, PartialEq)]
+pub enum SourceError {
+    /// The device could not be reached or the connection broke down
+    Network(String),
+    /// The device rejected the configured credentials
+    Auth(String),
+    /// The device answered with something that could not be understood
+    Parse(String),
+    /// The device did not answer in time
+    Timeout,
+    /// A local file, socket, command or plugin failed
+    Io(String),
+}
+
+impl SourceError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SourceError::Network(_) => "network",
+            SourceError::Auth(_) => "auth",
+            SourceError::Parse(_) => "parse",
+            SourceError::Timeout => "timeout",
+            SourceError::Io(_) => "io",
+        }
+    }
+
+    /// Rejected credentials and unexpected responses will not go away by asking again
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SourceError::Network(_) | SourceError::Timeout | SourceError::Io(_))
+    }
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Network(error) => write!(f, "network error: {}", error),
+            SourceError::Auth(error) => write!(f, "authentication failed: {}", error),
+            SourceError::Parse(error) => write!(f, "invalid response: {}", error),
+            SourceError::Timeout => write!(f, "timed out"),
+            SourceError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Published as `{"type": "network", "message": "network error: ..."}`
+impl Serialize for SourceError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("SourceError", 2)?;
+        error.serialize_field("type", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+impl From<reqwest::Error> for SourceError {
+    fn from(error: reqwest::Error) -> SourceError {
+        if error.is_timeout() {
+            SourceError::Timeout
+        } else {
+            SourceError::Network(error.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for SourceError {
+    fn from(error: serde_json::Error) -> SourceError {
+        SourceError::Parse(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for SourceError {
+    fn from(error: std::io::Error) -> SourceError {
+        SourceError::Io(error.to_string())
+    }
+}
@@ -1,143 +1,458 @@
 extern crate core;
 
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{Notify, Semaphore};
 use tokio::task::JoinSet;
 use tokio::time;
 
-use crate::device::{HPSwitch, OPNSense, UniFiController};
-
+mod circuit_breaker;
 mod config;
 mod device;
+mod error;
 mod mqtt_service;
 
-#[derive(Serialize)]
+use crate::circuit_breaker::{backoff_delay, BreakerState, CircuitBreaker};
+use crate::error::SourceError;
+
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
 pub struct DeviceLocation {
     ipv4: String,
     ipv6: Vec<String>,
     device_mac: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lease_expires: Option<u64>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    user: String,
     remote_ip: String,
     remote_mac: String,
+    /// The switch port the client is connected to, if the source knows it
+    #[serde(skip_serializing_if = "String::is_empty")]
+    port: String,
     location: String,
+    /// The name of the source that reported the location
+    #[serde(skip_serializing_if = "String::is_empty")]
+    source: String,
+    /// Set if the record is served from the cache because the last poll of its source failed,
+    /// never taken from clients reported by external commands or plugins
+    #[serde(skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    stale: bool,
 }
 
-pub enum DeviceTypes {
-    HPSwitch(HPSwitch),
-    UniFiController(UniFiController),
-    OPNSense(OPNSense),
+pub struct Source {
+    name: String,
+    device: Box<dyn Pollable>,
+    cache_ttl: Duration,
+    poll_interval: Duration,
+    poll_timeout: Duration,
+    retries: u32,
+    retry_backoff: Duration,
+    breaker: CircuitBreaker,
+    poll_trigger: Arc<Notify>,
 }
 
+/// A source is polled for all of its clients, sources receiving events can additionally
+/// push single changes in between through the `UpdateSender` passed to their factory
 #[async_trait]
-pub trait Pollable {
-    async fn poll_device(&self) -> Vec<DeviceLocation>;
+pub trait Pollable: Send + Sync {
+    async fn poll_device(&self) -> Result<Vec<DeviceLocation>, SourceError>;
+
+    /// The address of the polled device, events sent by it request an early poll through `PollTriggers`
+    fn address(&self) -> Option<IpAddr> {
+        None
+    }
+
+    /// The time limit a source enforces itself, e.g. for a command it runs.
+    /// The poll timeout of the source is raised to outlast it, so the source can clean up first.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
-impl DeviceTypes {
-    async fn get_device_locations(&self) -> Vec<DeviceLocation> {
-        match self {
-            DeviceTypes::UniFiController(unifi_controller) => {
-                unifi_controller.poll_device().await
-            }
-            DeviceTypes::HPSwitch(hp_switch) => {
-                hp_switch.poll_device().await
-            }
-            DeviceTypes::OPNSense(opnsense) => {
-                opnsense.poll_device().await
-            }
+/// Wakes the sources polling a device, e.g. when the device sent a link trap
+#[derive(Clone, Default)]
+pub struct PollTriggers {
+    triggers: Arc<Mutex<HashMap<IpAddr, Vec<Arc<Notify>>>>>,
+}
+
+impl PollTriggers {
+    pub fn add(&self, address: IpAddr, trigger: Arc<Notify>) {
+        self.triggers.lock().unwrap().entry(address).or_default().push(trigger);
+    }
+
+    pub fn trigger(&self, address: &IpAddr) {
+        if let Some(triggers) = self.triggers.lock().unwrap().get(address) {
+            triggers.iter().for_each(|trigger| trigger.notify_one());
         }
     }
 }
 
-struct MqttPublishData {
+/// An incremental change of a single client
+pub enum SourceUpdate {
+    Upsert(Box<DeviceLocation>),
+    Remove(String),
+}
+
+/// Sends the updates of one source to the core, which merges and publishes them right away
+#[derive(Clone)]
+pub struct UpdateSender {
+    source: String,
+    sender: UnboundedSender<(String, SourceUpdate)>,
+}
+
+impl UpdateSender {
+    pub fn upsert(&self, client: DeviceLocation) {
+        let _ = self.sender.send((self.source.to_string(), SourceUpdate::Upsert(Box::new(client))));
+    }
+
+    pub fn remove(&self, mac: &str) {
+        let _ = self.sender.send((self.source.to_string(), SourceUpdate::Remove(mac.to_string())));
+    }
+}
+
+/// The clients of a single source and the time of its last successful poll
+struct Snapshot {
+    clients: HashMap<String, DeviceLocation>,
+    updated: Instant,
+}
+
+/// The latest clients of every source, the polled snapshots with the pushed updates applied on top
+struct ClientTable {
     changed: bool,
-    clients: Vec<DeviceLocation>,
+    sources: Vec<String>,
+    cache_ttls: HashMap<String, Duration>,
+    snapshots: HashMap<String, Snapshot>,
+    errors_changed: bool,
+    errors: HashMap<String, SourceError>,
+    breakers_changed: bool,
+    breakers: HashMap<String, BreakerState>,
 }
 
-#[tokio::main]
-async fn main() {
-    let (devices, mut mqtt_service, scan_interval) = config::build_config();
+impl ClientTable {
+    fn new(sources: &[Source]) -> ClientTable {
+        ClientTable {
+            changed: false,
+            sources: sources.iter().map(|source| source.name.to_string()).collect(),
+            cache_ttls: sources.iter().map(|source| (source.name.to_string(), source.cache_ttl)).collect(),
+            snapshots: HashMap::with_capacity(sources.len()),
+            errors_changed: false,
+            errors: HashMap::new(),
+            breakers_changed: true,
+            breakers: sources.iter().map(|source| (source.name.to_string(), source.breaker.state())).collect(),
+        }
+    }
+
+    fn set_snapshot(&mut self, source: &str, clients: Vec<DeviceLocation>) {
+        let clients = clients.into_iter()
+            .map(|mut client| {
+                client.source = source.to_string();
+                (client.device_mac.to_string(), client)
+            })
+            .collect();
+
+        self.snapshots.insert(source.to_string(), Snapshot {
+            clients,
+            updated: Instant::now(),
+        });
+        self.changed = true;
 
-    let data_changed = Arc::new(
-        Mutex::new(
-            MqttPublishData {
-                changed: false,
-                clients: Vec::new(),
+        if self.errors.remove(source).is_some() {
+            self.errors_changed = true;
+        }
+    }
+
+    /// Records the error of a failed poll, the last clients of the source are kept as stale
+    /// records until its cache ttl has passed since the last successful poll
+    fn set_error(&mut self, source: &str, error: SourceError) {
+        let cache_ttl = self.cache_ttls.get(source).copied().unwrap_or_default();
+
+        match self.snapshots.get_mut(source) {
+            Some(snapshot) if snapshot.updated.elapsed() < cache_ttl => {
+                snapshot.clients.values_mut().for_each(|client| client.stale = true);
             }
-        )
-    );
-    let data_changed_ref = data_changed.clone();
+            _ => {
+                self.snapshots.remove(source);
+            }
+        }
+        self.changed = true;
 
-    let mut threads = JoinSet::new();
-    threads.spawn(async move {
-        loop {
-            println!("Polling devices...");
-
-            let mut total_clients: HashMap<String, DeviceLocation> = HashMap::new();
-            for device in devices.iter() {
-                let clients = time::timeout(
-                    Duration::from_millis(5000),
-                    device.get_device_locations(),
-                ).await;
-
-                if clients.is_ok() {
-                    let clients = clients.unwrap();
-
-                    for mut client in clients {
-                        let old_client = total_clients.get(&client.device_mac);
-                        if old_client.is_some() {
-                            let old_client = old_client.unwrap();
-                            if client.ipv4.is_empty() && !old_client.ipv4.is_empty() {
-                                client.ipv4 = old_client.ipv4.to_string();
-                            }
-                        }
-
-                        total_clients.insert(client.device_mac.to_string(), client);
-                    }
+        if self.errors.get(source) != Some(&error) {
+            self.errors.insert(source.to_string(), error);
+            self.errors_changed = true;
+        }
+    }
+
+    fn set_breaker_state(&mut self, source: &str, state: BreakerState) {
+        self.breakers.insert(source.to_string(), state);
+        self.breakers_changed = true;
+    }
+
+    fn apply_update(&mut self, source: &str, update: SourceUpdate) {
+        let snapshot = self.snapshots.entry(source.to_string()).or_insert_with(|| Snapshot {
+            clients: HashMap::new(),
+            updated: Instant::now(),
+        });
+
+        // an update repeating the stored record must not cause another publish
+        let changed = match update {
+            SourceUpdate::Upsert(mut client) => {
+                client.source = source.to_string();
+                let changed = snapshot.clients.get(&client.device_mac) != Some(&*client);
+                if changed {
+                    snapshot.clients.insert(client.device_mac.to_string(), *client);
                 }
+                changed
             }
+            SourceUpdate::Remove(mac) => snapshot.clients.remove(&mac).is_some(),
+        };
+        self.changed |= changed;
+    }
 
-            let clients = fill_missing_information(total_clients)
-                .into_iter()
-                .filter(|client| !client.remote_mac.is_empty())
-                .collect();
-            {
-                let mut data_changed = data_changed_ref.lock().unwrap();
-                data_changed.changed = true;
-                data_changed.clients = clients;
+    /// Merges the clients of all sources in the configured order
+    fn merged_clients(&self) -> Vec<DeviceLocation> {
+        let mut total_clients: HashMap<String, DeviceLocation> = HashMap::new();
+        for snapshot in self.sources.iter().filter_map(|source| self.snapshots.get(source)) {
+            for client in snapshot.clients.values() {
+                merge_client(&mut total_clients, client.clone());
             }
+        }
+
+        fill_missing_information(total_clients)
+            .into_iter()
+            .filter(|client| !client.remote_mac.is_empty() || !client.location.is_empty())
+            .collect()
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let poll_triggers = PollTriggers::default();
+    let (update_sender, mut updates) = mpsc::unbounded_channel();
+    let (sources, mut mqtt_service, max_concurrent_polls) = config::build_config(&poll_triggers, &update_sender);
+
+    let client_table = Arc::new(Mutex::new(ClientTable::new(&sources)));
+    let publish_trigger = Arc::new(Notify::new());
+
+    let poll_permits = Arc::new(Semaphore::new(max_concurrent_polls.max(1)));
+
+    let mut threads = JoinSet::new();
+
+    for source in sources {
+        threads.spawn(poll_source(
+            source,
+            client_table.clone(),
+            publish_trigger.clone(),
+            poll_permits.clone(),
+        ));
+    }
+
+    let table = client_table.clone();
+    let trigger = publish_trigger.clone();
+    threads.spawn(async move {
+        while let Some((source, update)) = updates.recv().await {
+            let mut table = table.lock().unwrap();
+            table.apply_update(&source, update);
 
-            time::sleep(Duration::from_secs(scan_interval)).await;
+            // apply everything already queued before publishing
+            while let Ok((source, update)) = updates.try_recv() {
+                table.apply_update(&source, update);
+            }
+            trigger.notify_one();
         }
     });
 
     threads.spawn(async move {
         loop {
             let text: Option<String>;
+            let errors: Option<String>;
+            let breakers: Option<String>;
             {
-                let mut data_changed = data_changed.lock().unwrap();
-                if data_changed.changed {
-                    data_changed.changed = false;
+                let mut table = client_table.lock().unwrap();
+                if table.changed {
+                    table.changed = false;
 
-                    text = Some(serde_json::to_string(&data_changed.clients).unwrap());
+                    text = Some(serde_json::to_string(&table.merged_clients()).unwrap());
                 } else {
                     text = None;
                 }
+
+                if table.errors_changed {
+                    table.errors_changed = false;
+
+                    errors = Some(serde_json::to_string(&table.errors).unwrap());
+                } else {
+                    errors = None;
+                }
+
+                if table.breakers_changed {
+                    table.breakers_changed = false;
+
+                    breakers = Some(serde_json::to_string(&table.breakers).unwrap());
+                } else {
+                    breakers = None;
+                }
             }
 
-            if text.is_some() {
+            if let Some(text) = text {
                 println!("Publishing mqtt data");
-                mqtt_service.publish(text.unwrap()).await;
+                mqtt_service.publish(text).await;
+            }
+            if let Some(errors) = errors {
+                mqtt_service.publish_status("errors", errors).await;
+            }
+            if let Some(breakers) = breakers {
+                mqtt_service.publish_status("breakers", breakers).await;
             }
 
-            mqtt_service.process_packets().await;
+            tokio::select! {
+                _ = mqtt_service.process_packets() => {}
+                _ = publish_trigger.notified() => {}
+            }
         }
     });
 
-    while let Some(_) = threads.join_next().await {}
+    while threads.join_next().await.is_some() {}
+}
+
+/// Polls a single source in its own interval, at most `max_concurrent_polls` sources are polled at the same time.
+/// Every result is merged into the client table as soon as it arrives.
+async fn poll_source(
+    mut source: Source,
+    client_table: Arc<Mutex<ClientTable>>,
+    publish_trigger: Arc<Notify>,
+    poll_permits: Arc<Semaphore>,
+) {
+    loop {
+        // a half-open breaker gets a single probe without retries
+        let retries = if source.breaker.state() == BreakerState::HalfOpen { 0 } else { source.retries };
+
+        let Some(result) = poll_with_retries(&source, retries, &poll_permits).await else {
+            return;
+        };
+
+        let state_change = match result {
+            Ok(clients) => {
+                client_table.lock().unwrap().set_snapshot(&source.name, clients);
+                source.breaker.record_success()
+            }
+            Err(error) => {
+                println!("{}: {}", source.name, error);
+                client_table.lock().unwrap().set_error(&source.name, error);
+                source.breaker.record_failure()
+            }
+        };
+
+        if let Some(state) = state_change {
+            set_breaker_state(&source, state, &client_table);
+        }
+        publish_trigger.notify_one();
+
+        if source.breaker.state() == BreakerState::Open {
+            time::sleep(source.breaker.cooldown()).await;
+
+            if let Some(state) = source.breaker.half_open() {
+                set_breaker_state(&source, state, &client_table);
+                publish_trigger.notify_one();
+            }
+            continue;
+        }
+
+        // sources receiving events from the polled device can request an early poll
+        tokio::select! {
+            _ = time::sleep(source.poll_interval) => {}
+            _ = source.poll_trigger.notified() => {}
+        }
+    }
+}
+
+/// Retries transient errors with a jittered exponential backoff, the permit is only held while polling.
+/// Returns `None` once the semaphore is closed.
+async fn poll_with_retries(source: &Source, retries: u32, poll_permits: &Semaphore) -> Option<Result<Vec<DeviceLocation>, SourceError>> {
+    let mut attempt = 0;
+
+    loop {
+        let result = {
+            let _permit = poll_permits.acquire().await.ok()?;
+
+            time::timeout(source.poll_timeout, source.device.poll_device())
+                .await
+                .unwrap_or(Err(SourceError::Timeout))
+        };
+
+        match result {
+            Err(error) if error.is_transient() && attempt < retries => {
+                let delay = backoff_delay(source.retry_backoff, attempt);
+                attempt += 1;
+
+                println!("{}: {}...retrying in {}ms ({}/{})", source.name, error, delay.as_millis(), attempt, retries);
+                time::sleep(delay).await;
+            }
+            result => return Some(result),
+        }
+    }
+}
+
+fn set_breaker_state(source: &Source, state: BreakerState, client_table: &Mutex<ClientTable>) {
+    match state {
+        BreakerState::Open => println!("{}: circuit breaker open, pausing polls for {}s", source.name, source.breaker.cooldown().as_secs()),
+        state => println!("{}: circuit breaker {}", source.name, state),
+    }
+
+    client_table.lock().unwrap().set_breaker_state(&source.name, state);
+}
+
+fn merge_client(total_clients: &mut HashMap<String, DeviceLocation>, mut client: DeviceLocation) {
+    if let Some(old_client) = total_clients.get_mut(&client.device_mac) {
+        // records without any location information only contribute their addresses
+        if client.location.is_empty() && client.remote_ip.is_empty() && client.remote_mac.is_empty() {
+            if old_client.ipv4.is_empty() {
+                old_client.ipv4 = client.ipv4;
+            }
+            if old_client.ipv6.is_empty() {
+                old_client.ipv6 = client.ipv6;
+            }
+            if old_client.hostname.is_empty() {
+                old_client.hostname = client.hostname;
+            }
+            if old_client.lease_expires.is_none() {
+                old_client.lease_expires = client.lease_expires;
+            }
+            if old_client.user.is_empty() {
+                old_client.user = client.user;
+            }
+            return;
+        }
+
+        if client.ipv4.is_empty() && !old_client.ipv4.is_empty() {
+            client.ipv4 = old_client.ipv4.to_string();
+        }
+        if client.hostname.is_empty() && !old_client.hostname.is_empty() {
+            client.hostname = old_client.hostname.to_string();
+        }
+        if client.lease_expires.is_none() {
+            client.lease_expires = old_client.lease_expires;
+        }
+        if client.user.is_empty() && !old_client.user.is_empty() {
+            client.user = old_client.user.to_string();
+        }
+        // presence sources only know the room, keep the network side of the client
+        if client.remote_ip.is_empty() && client.remote_mac.is_empty() {
+            client.remote_ip = old_client.remote_ip.to_string();
+            client.remote_mac = old_client.remote_mac.to_string();
+            client.port = old_client.port.to_string();
+        }
+    }
+
+    total_clients.insert(client.device_mac.to_string(), client);
 }
 
 fn fill_missing_information(mut clients: HashMap<String, DeviceLocation>) -> Vec<DeviceLocation> {
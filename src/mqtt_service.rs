@@ -1,12 +1,19 @@
 use std::time::{Duration};
 
-use rumqttc::{AsyncClient, EventLoop, MqttOptions, QoS, TlsConfiguration, Transport};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS, TlsConfiguration, Transport};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio::time;
 
+struct MqttSubscription {
+    topic: String,
+    sender: UnboundedSender<Publish>,
+}
+
 pub struct MqttService {
     mqtt_client: AsyncClient,
     mqtt_events: EventLoop,
     topic: String,
+    subscriptions: Vec<MqttSubscription>,
 }
 
 impl MqttService {
@@ -28,14 +35,38 @@ impl MqttService {
             mqtt_client,
             mqtt_events,
             topic: topic.to_string(),
+            subscriptions: Vec::new(),
         }
     }
 
+    /// Registers a topic filter. Matching messages are forwarded to the returned receiver,
+    /// the subscription itself is (re-)sent to the broker on every connect.
+    pub fn subscribe(&mut self, topic: &str) -> UnboundedReceiver<Publish> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        self.subscriptions.push(MqttSubscription {
+            topic: topic.to_string(),
+            sender,
+        });
+
+        receiver
+    }
+
+    pub fn client(&self) -> AsyncClient {
+        self.mqtt_client.clone()
+    }
+
     pub async fn process_packets(&mut self) {
-        let _result = time::timeout(
+        let result = time::timeout(
             Duration::from_millis(2000),
             self.mqtt_events.poll(),
         ).await;
+
+        match result {
+            Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => self.send_subscriptions(),
+            Ok(Ok(Event::Incoming(Packet::Publish(publish)))) => self.dispatch(publish),
+            _ => {}
+        }
     }
 
     pub async fn publish(&mut self, data: String) {
@@ -44,4 +75,28 @@ impl MqttService {
             self.mqtt_client.publish(&self.topic, QoS::AtMostOnce, false, data),
         ).await;
     }
-}
\ No newline at end of file
+
+    /// Publishes source status like errors to `<topic>/<subtopic>`, retained so late subscribers see it
+    pub async fn publish_status(&mut self, subtopic: &str, data: String) {
+        let _ = time::timeout(
+            Duration::from_millis(2000),
+            self.mqtt_client.publish(format!("{}/{}", self.topic, subtopic), QoS::AtMostOnce, true, data),
+        ).await;
+    }
+
+    fn send_subscriptions(&self) {
+        for subscription in self.subscriptions.iter() {
+            if let Err(error) = self.mqtt_client.try_subscribe(&subscription.topic, QoS::AtLeastOnce) {
+                println!("failed to subscribe to {}: {}", subscription.topic, error);
+            }
+        }
+    }
+
+    fn dispatch(&self, publish: Publish) {
+        for subscription in self.subscriptions.iter() {
+            if rumqttc::matches(&publish.topic, &subscription.topic) {
+                let _ = subscription.sender.send(publish.clone());
+            }
+        }
+    }
+}